@@ -1,100 +1,199 @@
-use std::io::{BufRead, stdin};
 use std::collections::HashSet;
+use std::env;
+use std::io::{BufRead, stdin};
+use std::ops::Range;
+use std::process;
 
-fn main() {
-    let seat_codes: Vec<String> = stdin().lock().lines()
-        .map(|maybe_line| maybe_line.expect("Error while reading line"))
-        .collect();
+type SeatId = u32;
+
+const ROWS: u32 = 128;
+const COLUMNS: u32 = 8;
 
-    part1(&seat_codes);
-    part2(&seat_codes);
+// Columns 0..AISLE are left of the aisle, AISLE..COLUMNS right of it
+const AISLE: u32 = 4;
+
+struct SeatMap {
+    // Indexed by seat id
+    occupied: Vec<bool>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl SeatMap {
+    fn from(seat_codes: &[String]) -> Self {
+        let mut occupied = vec![false; (ROWS * COLUMNS) as usize];
 
-    #[test]
-    fn determine_seat_id_returns_357_for_FBFBBFFRLR() {
-        let seat_code = String::from("FBFBBFFRLR");
-        assert_eq!(determine_seat_id(&seat_code), 357);
+        for seat_code in seat_codes {
+            occupied[determine_seat_id(seat_code) as usize] = true;
+        }
+
+        Self {
+            occupied,
+        }
     }
 
-    #[test]
-    fn determine_seat_id_returns_567_for_BFFFBBFRRR() {
-        let seat_code = String::from("BFFFBBFRRR");
-        assert_eq!(determine_seat_id(&seat_code), 567);
+    fn is_occupied(&self, row: u32, column: u32) -> bool {
+        self.occupied[(row * COLUMNS + column) as usize]
     }
 
-    #[test]
-    fn determine_seat_id_returns_567_for_FFFBBBFRRR() {
-        let seat_code = String::from("FFFBBBFRRR");
-        assert_eq!(determine_seat_id(&seat_code), 119);
+    /// Your seat is the only free one whose neighbors (by seat id) are both occupied.
+    fn your_seat(&self) -> Option<SeatId> {
+        (1..ROWS * COLUMNS - 1)
+            .find(|&seat_id| !self.occupied[seat_id as usize]
+                && self.occupied[seat_id as usize - 1]
+                && self.occupied[seat_id as usize + 1]
+            )
     }
 
-    #[test]
-    fn determine_seat_id_returns_820_for_BBFFBBFRLL() {
-        let seat_code = String::from("BBFFBBFRLL");
-        assert_eq!(determine_seat_id(&seat_code), 820);
+    fn row_occupancy(&self) -> Vec<usize> {
+        (0..ROWS)
+            .map(|row| (0..COLUMNS).filter(|&column| self.is_occupied(row, column)).count())
+            .collect()
+    }
+
+    fn column_occupancy(&self) -> Vec<usize> {
+        (0..COLUMNS)
+            .map(|column| (0..ROWS).filter(|&row| self.is_occupied(row, column)).count())
+            .collect()
     }
-}
 
-fn determine_seat_id(seat_code: &String) -> u32 {
-    let row_partitions = &seat_code[..7];
-    let column_partitions = &seat_code[7..];
+    /// Returns the seat ids of the longest run of consecutive free seats; the first one wins on ties.
+    fn longest_free_run(&self) -> Range<SeatId> {
+        let mut longest = 0..0;
+        let mut start = 0;
 
-    let mut lower_bound = 0;
-    let mut upper_bound = 127;
-    let mut row = 0;
-
-    for row_partition in row_partitions.chars() {
-        // println!("before: l={0} u={1} p={2}", lower_bound, upper_bound, row_partition);
-        match row_partition {
-            'F' => {
-                upper_bound = (upper_bound + lower_bound) / 2;
-                row = lower_bound;
+        for seat_id in 0..=ROWS * COLUMNS {
+            if seat_id < ROWS * COLUMNS && !self.occupied[seat_id as usize] {
+                continue;
             }
-            'B' => {
-                // Need to add 1 as the row indices are 0-based
-                lower_bound = (upper_bound + lower_bound + 1) / 2;
-                row = upper_bound;
+
+            if seat_id - start > longest.end - longest.start {
+                longest = start..seat_id;
             }
-            _ => panic!("Invalid row partition '{0}'", row_partition)
+
+            start = seat_id + 1;
         }
-        // println!("after: l={0} u={1} p={2}", lower_bound, upper_bound, row_partition);
+
+        longest
     }
 
-    // println!("row={0}", row);
+    fn to_ascii(&self) -> Vec<String> {
+        let your_seat = self.your_seat();
 
-    let mut lower_bound = 0;
-    let mut upper_bound = 7;
-    let mut column = 0;
-
-    for column_partition in column_partitions.chars() {
-        // println!("before: l={0} u={1} p={2}", lower_bound, upper_bound, column_partition);
-        match column_partition {
-            'L' => {
-                upper_bound = (upper_bound + lower_bound) / 2;
-                column = lower_bound;
+        let mut result = Vec::new();
+        result.push(String::from("    ABCD EFGH"));
+
+        for row in 0..ROWS {
+            let mut line = format!("{0:>3} ", row);
+
+            for column in 0..COLUMNS {
+                if column == AISLE {
+                    line.push(' ');
+                }
+
+                line.push(if your_seat == Some(row * COLUMNS + column) {
+                    'X'
+                } else if self.is_occupied(row, column) {
+                    '#'
+                } else {
+                    '.'
+                });
             }
-            'R' => {
-                // Need to add 1 as the column indices are 0-based
-                lower_bound = (upper_bound + lower_bound + 1) / 2;
-                column = upper_bound;
+
+            result.push(line);
+        }
+
+        result
+    }
+
+    /// Renders the map as a plain PPM (P3) image with one pixel per seat.
+    fn to_ppm(&self) -> String {
+        let your_seat = self.your_seat();
+
+        let mut result = format!("P3\n{0} {1}\n255\n", COLUMNS, ROWS);
+
+        for row in 0..ROWS {
+            let pixels: Vec<&str> = (0..COLUMNS)
+                .map(|column| if your_seat == Some(row * COLUMNS + column) {
+                    "255 0 0"
+                } else if self.is_occupied(row, column) {
+                    "64 64 64"
+                } else {
+                    "255 255 255"
+                })
+                .collect();
+
+            result.push_str(&pixels.join("  "));
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+fn main() {
+    let seat_codes: Vec<String> = stdin().lock().lines()
+        .map(|maybe_line| maybe_line.expect("Error while reading line"))
+        .collect();
+
+    match env::args().nth(1).as_deref() {
+        None => {
+            part1(&seat_codes);
+            part2(&seat_codes);
+        }
+        Some("map") => {
+            for line in SeatMap::from(&seat_codes).to_ascii() {
+                println!("{0}", line);
             }
-            _ => panic!("Invalid column partition '{0}'", column_partition)
         }
-        // println!("after: l={0} u={1} p={2}", lower_bound, upper_bound, column_partition);
+        Some("ppm") => print!("{0}", SeatMap::from(&seat_codes).to_ppm()),
+        Some("stats") => print_stats(&SeatMap::from(&seat_codes)),
+        Some(mode) => {
+            eprintln!("Unknown mode: {0} (expected one of: map, ppm, stats)", mode);
+            process::exit(1);
+        }
     }
+}
+
+fn decode_partitions(partitions: &str, lower: char, upper: char, size: u32) -> u32 {
+    let mut lower_bound = 0;
+    let mut upper_bound = size - 1;
+    let mut result = 0;
 
-    // println!("column={0}", column);
+    for partition in partitions.chars() {
+        // println!("before: l={0} u={1} p={2}", lower_bound, upper_bound, partition);
+        if partition == lower {
+            upper_bound = (upper_bound + lower_bound) / 2;
+            result = lower_bound;
+        } else if partition == upper {
+            // Need to round up as the indices are 0-based
+            lower_bound = (upper_bound + lower_bound).div_ceil(2);
+            result = upper_bound;
+        } else {
+            panic!("Invalid partition '{0}' (expected '{1}' or '{2}')", partition, lower, upper);
+        }
+        // println!("after: l={0} u={1} p={2}", lower_bound, upper_bound, partition);
+    }
 
-    row * 8 + column
+    result
 }
 
-fn part1(seat_codes: &Vec<String>) -> u32 {
-    let highest_seat_id = seat_codes.into_iter()
-        .map(|seat_code| determine_seat_id(&seat_code))
+fn decode_seat(seat_code: &str) -> (u32, u32) {
+    let row = decode_partitions(&seat_code[..7], 'F', 'B', ROWS);
+    let column = decode_partitions(&seat_code[7..], 'L', 'R', COLUMNS);
+
+    // println!("row={0} column={1}", row, column);
+
+    (row, column)
+}
+
+fn determine_seat_id(seat_code: &str) -> SeatId {
+    let (row, column) = decode_seat(seat_code);
+
+    row * COLUMNS + column
+}
+
+fn part1(seat_codes: &[String]) -> u32 {
+    let highest_seat_id = seat_codes.iter()
+        .map(|seat_code| determine_seat_id(seat_code))
         .max()
         .expect("Could not determine max seat id");
 
@@ -103,7 +202,7 @@ fn part1(seat_codes: &Vec<String>) -> u32 {
     highest_seat_id
 }
 
-fn part2(seat_codes: &Vec<String>) {
+fn part2(seat_codes: &[String]) {
     let mut possible_seats: HashSet<u32> = HashSet::new();
     for row in 0..127 {
         for column in 0..7 {
@@ -111,8 +210,8 @@ fn part2(seat_codes: &Vec<String>) {
         }
     }
 
-    let seat_codes = seat_codes.into_iter()
-        .map(|seat_code| determine_seat_id(&seat_code))
+    seat_codes.iter()
+        .map(|seat_code| determine_seat_id(seat_code))
         .for_each(|seat_id| {
             possible_seats.remove(&seat_id);
         });
@@ -128,3 +227,107 @@ fn part2(seat_codes: &Vec<String>) {
     // part2: your seat id = [0, 1, 2, 3, 4, 5, 6, 619, 909, 910, 912, 913, 914, 915, 916, 917, 918, 920, 921, 922, 923, 924, 925, 926, 928, 929, 930, 931, 932, 933, 934, 936, 937, 938, 939, 940, 941, 942, 944, 945, 946, 947, 948, 949, 950, 952, 953, 954, 955, 956, 957, 958, 960, 961, 962, 963, 964, 965, 966, 968, 969, 970, 971, 972, 973, 974, 976, 977, 978, 979, 980, 981, 982, 984, 985, 986, 987, 988, 989, 990, 992, 993, 994, 995, 996, 997, 998, 1000, 1001, 1002, 1003, 1004, 1005, 1006, 1008, 1009, 1010, 1011, 1012, 1013, 1014]
     // and guessed "619" :)
 }
+
+fn print_stats(seat_map: &SeatMap) {
+    let occupied = seat_map.occupied.iter()
+        .filter(|&&occupied| occupied)
+        .count();
+
+    println!("occupied={0} free={1}", occupied, seat_map.occupied.len() - occupied);
+    println!("your seat = {0:?}", seat_map.your_seat());
+
+    for (row, count) in seat_map.row_occupancy().iter().enumerate() {
+        println!("row {0:>3}: {1}/{2}", row, count, COLUMNS);
+    }
+
+    for (column, count) in seat_map.column_occupancy().iter().enumerate() {
+        println!("column {0}: {1}/{2}", column, count, ROWS);
+    }
+
+    let run = seat_map.longest_free_run();
+    println!("longest free run: {0} seats, seat ids {1:?}", run.end - run.start, run);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn determine_seat_id_returns_357_for_FBFBBFFRLR() {
+        let seat_code = String::from("FBFBBFFRLR");
+        assert_eq!(determine_seat_id(&seat_code), 357);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn determine_seat_id_returns_567_for_BFFFBBFRRR() {
+        let seat_code = String::from("BFFFBBFRRR");
+        assert_eq!(determine_seat_id(&seat_code), 567);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn determine_seat_id_returns_567_for_FFFBBBFRRR() {
+        let seat_code = String::from("FFFBBBFRRR");
+        assert_eq!(determine_seat_id(&seat_code), 119);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn determine_seat_id_returns_820_for_BBFFBBFRLL() {
+        let seat_code = String::from("BBFFBBFRLL");
+        assert_eq!(determine_seat_id(&seat_code), 820);
+    }
+
+    fn seat_codes(codes: &[&str]) -> Vec<String> {
+        codes.iter()
+            .map(|code| String::from(*code))
+            .collect()
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn decode_seat_returns_row_44_column_5_for_FBFBBFFRLR() {
+        assert_eq!(decode_seat("FBFBBFFRLR"), (44, 5));
+    }
+
+    #[test]
+    fn seat_map_finds_your_seat_between_two_occupied_seats() {
+        // Seat ids 8 and 10 are occupied, 9 is free
+        let seat_map = SeatMap::from(&seat_codes(&["FFFFFFBLLL", "FFFFFFBLRL"]));
+
+        assert_eq!(seat_map.your_seat(), Some(9));
+    }
+
+    #[test]
+    fn seat_map_row_and_column_occupancy() {
+        let seat_map = SeatMap::from(&seat_codes(&["FFFFFFBLLL", "FFFFFFBLRL", "BBBBBBBRRR"]));
+
+        let rows = seat_map.row_occupancy();
+        assert_eq!(rows.len(), 128);
+        assert_eq!(rows[1], 2);
+        assert_eq!(rows[127], 1);
+        assert_eq!(rows.iter().sum::<usize>(), 3);
+
+        assert_eq!(seat_map.column_occupancy(), vec![1, 0, 1, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn seat_map_longest_free_run() {
+        // Occupies seat ids 8, 10 and 1023
+        let seat_map = SeatMap::from(&seat_codes(&["FFFFFFBLLL", "FFFFFFBLRL", "BBBBBBBRRR"]));
+
+        assert_eq!(seat_map.longest_free_run(), 11..1023);
+    }
+
+    #[test]
+    fn seat_map_to_ascii_marks_your_seat() {
+        let seat_map = SeatMap::from(&seat_codes(&["FFFFFFBLLL", "FFFFFFBLRL"]));
+
+        let ascii = seat_map.to_ascii();
+
+        assert_eq!(ascii.len(), 129);
+        assert_eq!(ascii[2], "  1 #X#. ....");
+    }
+}
@@ -1,71 +1,426 @@
-use std::collections::{HashSet, HashMap};
+use std::env;
+use std::fmt;
 use std::io::{BufRead, stdin};
+use std::process;
+use std::str::FromStr;
 
-fn main() {
-    let mut groups_answers: Vec<String> = stdin().lock().lines()
-        .map(|maybe_line| maybe_line.expect("Error while reading line"))
-        .collect();
+const QUESTIONS: usize = 26;
+
+/// The questions a single person (or a whole group) answered "yes" to, one bit per question 'a'..='z'.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct AnswerSet(u32);
+
+impl AnswerSet {
+    const ALL: AnswerSet = AnswerSet((1 << QUESTIONS) - 1);
 
-    // Add empty line so the last entry is always processed (as we expect empty lines to trigger the validation)
-    groups_answers.push(String::from(""));
+    fn from_answers(answers: &str) -> Self {
+        let bits = answers.chars()
+            .filter_map(question_index)
+            .fold(0, |bits, index| bits | 1 << index);
+
+        AnswerSet(bits)
+    }
+
+    fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
 
-    let groups_answers = groups_answers;
+    fn contains(&self, index: usize) -> bool {
+        self.0 & 1 << index != 0
+    }
 
-    println!("groups_answers = {0:?}", groups_answers);
+    fn union(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 | other.0)
+    }
 
-    part1(&groups_answers);
-    part2(&groups_answers);
+    fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 & other.0)
+    }
 }
 
-fn part1(answers: &Vec<String>) -> usize {
-    let mut questions_answered_yes = 0;
+fn question_index(question: char) -> Option<usize> {
+    if question.is_ascii_lowercase() {
+        Some((question as u8 - b'a') as usize)
+    } else {
+        None
+    }
+}
 
-    let mut group_answers = HashSet::new();
+fn index_question(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
 
-    for answer in answers {
-        let answer = answer.trim();
+#[derive(Clone, Debug, Default)]
+struct Group {
+    // 1-based line number of the group's first member
+    line: usize,
+    members: Vec<AnswerSet>,
+}
 
-        if answer.is_empty() {
-            questions_answered_yes = questions_answered_yes + group_answers.len();
-            group_answers.clear();
-        } else {
-            for answer in answer.chars() {
-                group_answers.insert(answer);
+impl Group {
+    fn union(&self) -> AnswerSet {
+        self.members.iter()
+            .fold(AnswerSet::default(), |result, member| result.union(member))
+    }
+
+    fn intersection(&self) -> AnswerSet {
+        self.members.iter()
+            .fold(AnswerSet::ALL, |result, member| result.intersection(member))
+    }
+
+    /// How many members answered each question with "yes".
+    fn answer_counts(&self) -> [usize; QUESTIONS] {
+        let mut counts = [0; QUESTIONS];
+
+        for member in &self.members {
+            for (index, count) in counts.iter_mut().enumerate() {
+                if member.contains(index) {
+                    *count += 1;
+                }
             }
         }
+
+        counts
     }
 
-    println!("part1: questions_answered_yes = {0}", questions_answered_yes);
+    fn answered_by(&self, condition: impl Fn(usize) -> bool) -> AnswerSet {
+        let bits = self.answer_counts().iter()
+            .enumerate()
+            .filter(|&(_index, &count)| condition(count))
+            .fold(0, |bits, (index, _count)| bits | 1 << index);
 
-    questions_answered_yes
+        AnswerSet(bits)
+    }
 }
 
-fn part2(answers: &Vec<String>) -> usize {
-    let mut questions_answered_yes = 0;
+#[derive(Clone, Debug, PartialEq)]
+enum Query {
+    /// Questions anyone in the group answered
+    Union,
+    /// Questions everyone in the group answered
+    Intersection,
+    /// Questions answered by at least k members
+    AtLeast(usize),
+    /// Questions answered by exactly one member
+    ExactlyOne,
+}
 
-    let mut group_answers = HashMap::new();
-    let mut group_size = 0;
+impl Query {
+    fn evaluate(&self, group: &Group) -> AnswerSet {
+        match self {
+            Query::Union => group.union(),
+            Query::Intersection => {
+                if group.members.is_empty() {
+                    AnswerSet::default()
+                } else {
+                    group.intersection()
+                }
+            }
+            Query::AtLeast(k) => group.answered_by(|count| count > 0 && count >= *k),
+            Query::ExactlyOne => group.answered_by(|count| count == 1),
+        }
+    }
+}
 
-    for answer in answers {
-        let answer = answer.trim();
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" | "any" => Ok(Query::Union),
+            "intersection" | "all" => Ok(Query::Intersection),
+            "exactly-one" => Ok(Query::ExactlyOne),
+            _ => match s.strip_prefix("at-least:") {
+                Some(k) => k.parse::<usize>()
+                    .map(Query::AtLeast)
+                    .map_err(|_| format!("Invalid member count in query: {0}", s)),
+                None => Err(format!("Unknown query: {0} (expected union, intersection, at-least:<k> or exactly-one)", s)),
+            },
+        }
+    }
+}
 
-        if answer.is_empty() {
-            questions_answered_yes += group_answers.iter()
-                .filter(|&(_question, count)| *count == group_size)
-                .count();
+fn parse_groups(lines: &[String]) -> Vec<Group> {
+    let mut groups = Vec::new();
+    let mut group = Group::default();
 
-            group_answers.clear();
-            group_size = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            if !group.members.is_empty() {
+                groups.push(group);
+                group = Group::default();
+            }
         } else {
-            group_size += 1;
+            if group.members.is_empty() {
+                group.line = index + 1;
+            }
+
+            group.members.push(AnswerSet::from_answers(line));
+        }
+    }
+
+    if !group.members.is_empty() {
+        groups.push(group);
+    }
+
+    groups
+}
+
+#[derive(Debug, PartialEq)]
+enum ValidationIssue {
+    InvalidQuestion { line: usize, question: char },
+    DuplicateAnswer { line: usize, question: char },
+    EmptyGroup { line: usize },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::InvalidQuestion { line, question } =>
+                write!(f, "line {0}: invalid question {1:?} (expected a..=z)", line, question),
+            ValidationIssue::DuplicateAnswer { line, question } =>
+                write!(f, "line {0}: question '{1}' answered more than once", line, question),
+            ValidationIssue::EmptyGroup { line } =>
+                write!(f, "line {0}: empty group", line),
+        }
+    }
+}
+
+/// Checks the raw input for problems that `parse_groups` silently glosses over.
+fn validate(lines: &[String]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let mut previous_line_empty = true;
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            if previous_line_empty {
+                issues.push(ValidationIssue::EmptyGroup { line: line_number });
+            }
+
+            previous_line_empty = true;
+            continue;
+        }
+
+        previous_line_empty = false;
+
+        let mut seen = AnswerSet::default();
+
+        for question in line.chars() {
+            match question_index(question) {
+                None => issues.push(ValidationIssue::InvalidQuestion { line: line_number, question }),
+                Some(index) if seen.contains(index) =>
+                    issues.push(ValidationIssue::DuplicateAnswer { line: line_number, question }),
+                Some(index) => seen = seen.union(&AnswerSet(1 << index)),
+            }
+        }
+    }
+
+    issues
+}
+
+/// Sums the number of questions matching `query` over all groups.
+fn count(groups: &[Group], query: &Query) -> usize {
+    groups.iter()
+        .map(|group| query.evaluate(group).len())
+        .sum()
+}
+
+/// For each question, the number of groups for which `query` contains it.
+fn histogram(groups: &[Group], query: &Query) -> [usize; QUESTIONS] {
+    let mut histogram = [0; QUESTIONS];
+
+    for group in groups {
+        let result = query.evaluate(group);
 
-            for answer in answer.chars() {
-                *group_answers.entry(answer).or_insert(0) += 1;
+        for (index, count) in histogram.iter_mut().enumerate() {
+            if result.contains(index) {
+                *count += 1;
             }
         }
     }
 
+    histogram
+}
+
+fn main() {
+    let groups_answers: Vec<String> = stdin().lock().lines()
+        .map(|maybe_line| maybe_line.expect("Error while reading line"))
+        .collect();
+
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let strict = args.iter().any(|arg| arg == "--strict");
+    args.retain(|arg| arg != "--strict");
+
+    let issues = validate(&groups_answers);
+
+    for issue in &issues {
+        eprintln!("{0}: {1}", if strict { "error" } else { "warning" }, issue);
+    }
+
+    if strict && !issues.is_empty() {
+        eprintln!("Rejecting input with {0} validation issue(s)", issues.len());
+        process::exit(1);
+    }
+
+    let groups = parse_groups(&groups_answers);
+
+    match args.first().map(String::as_str) {
+        None => {
+            part1(&groups);
+            part2(&groups);
+        }
+        Some("count") => {
+            let query = parse_query_arg(args.get(1));
+            println!("count({0:?}) = {1}", query, count(&groups, &query));
+        }
+        Some("histogram") => {
+            let query = parse_query_arg(args.get(1));
+            let histogram = histogram(&groups, &query);
+
+            println!("histogram({0:?}) over {1} groups:", query, groups.len());
+            for (index, count) in histogram.iter().enumerate() {
+                println!("{0}: {1}", index_question(index), count);
+            }
+        }
+        Some("report") => {
+            for (index, group) in groups.iter().enumerate() {
+                println!("group {0:>4} (line {1:>4}): size={2} union={3} intersection={4}",
+                         index + 1, group.line, group.members.len(),
+                         Query::Union.evaluate(group).len(), Query::Intersection.evaluate(group).len()
+                );
+            }
+        }
+        Some(command) => exit_with_usage(&format!("Unknown command: {0}", command)),
+    }
+}
+
+fn parse_query_arg(arg: Option<&String>) -> Query {
+    match arg {
+        Some(arg) => arg.parse::<Query>().unwrap_or_else(|error| exit_with_usage(&error)),
+        None => Query::Union,
+    }
+}
+
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day06 [--strict] [report|count|histogram [union|intersection|at-least:<k>|exactly-one]] < input");
+    process::exit(1);
+}
+
+fn part1(groups: &[Group]) -> usize {
+    let questions_answered_yes = count(groups, &Query::Union);
+
+    println!("part1: questions_answered_yes = {0}", questions_answered_yes);
+
+    questions_answered_yes
+}
+
+fn part2(groups: &[Group]) -> usize {
+    let questions_answered_yes = count(groups, &Query::Intersection);
+
     println!("part2: questions_answered_yes = {0}", questions_answered_yes);
 
     questions_answered_yes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example1_groups() -> Vec<Group> {
+        let lines: Vec<String> = "abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb"
+            .split('\n')
+            .map(String::from)
+            .collect();
+
+        parse_groups(&lines)
+    }
+
+    #[test]
+    fn answer_set_from_answers() {
+        let answers = AnswerSet::from_answers("abcz");
+
+        assert_eq!(answers.len(), 4);
+        assert!(answers.contains(0) && answers.contains(25));
+        assert!(!answers.contains(3));
+    }
+
+    #[test]
+    fn parse_groups_example1() {
+        let groups = example1_groups();
+
+        let sizes: Vec<usize> = groups.iter()
+            .map(|group| group.members.len())
+            .collect();
+
+        assert_eq!(sizes, vec![1, 3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn parse_groups_records_first_line() {
+        let lines: Vec<usize> = example1_groups().iter()
+            .map(|group| group.line)
+            .collect();
+
+        assert_eq!(lines, vec![1, 3, 7, 10, 15]);
+    }
+
+    #[test]
+    fn validate_accepts_example1() {
+        let lines: Vec<String> = "abc\n\na\nb".split('\n').map(String::from).collect();
+
+        assert_eq!(validate(&lines), vec![]);
+    }
+
+    #[test]
+    fn validate_reports_invalid_duplicate_and_empty() {
+        let lines: Vec<String> = "aB\n\n\naa".split('\n').map(String::from).collect();
+
+        assert_eq!(validate(&lines), vec![
+            ValidationIssue::InvalidQuestion { line: 1, question: 'B' },
+            ValidationIssue::EmptyGroup { line: 3 },
+            ValidationIssue::DuplicateAnswer { line: 4, question: 'a' },
+        ]);
+    }
+
+    #[test]
+    fn part1_example1() {
+        assert_eq!(part1(&example1_groups()), 11);
+    }
+
+    #[test]
+    fn part2_example1() {
+        assert_eq!(part2(&example1_groups()), 6);
+    }
+
+    #[test]
+    fn at_least_and_exactly_one_queries() {
+        let group = &example1_groups()[2];
+
+        assert_eq!(Query::AtLeast(1).evaluate(group), group.union());
+        assert_eq!(Query::AtLeast(2).evaluate(group), AnswerSet::from_answers("a"));
+        assert_eq!(Query::AtLeast(3).evaluate(group), AnswerSet::default());
+        assert_eq!(Query::ExactlyOne.evaluate(group), AnswerSet::from_answers("bc"));
+    }
+
+    #[test]
+    fn histogram_counts_groups_per_question() {
+        let histogram = histogram(&example1_groups(), &Query::Intersection);
+
+        assert_eq!(&histogram[..4], &[3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn query_from_str() {
+        assert_eq!("union".parse::<Query>(), Ok(Query::Union));
+        assert_eq!("at-least:3".parse::<Query>(), Ok(Query::AtLeast(3)));
+        assert!("at-least:x".parse::<Query>().is_err());
+        assert!("median".parse::<Query>().is_err());
+    }
+}
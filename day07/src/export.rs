@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::graph::{BagError, BagGraph, Color, Content};
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    /// Graphviz DOT, edges labeled with the number of inner bags
+    Dot,
+    /// JSON object mapping each color to an object of `inner color: count`
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown export format: {0} (expected dot or json)", s)),
+        }
+    }
+}
+
+/// Which part of the graph to export.
+pub enum Scope {
+    All,
+    /// The color and every bag it (transitively) contains
+    From(Color),
+    /// The color and every bag that (transitively) contains it
+    To(Color),
+}
+
+pub fn export(graph: &BagGraph, format: &Format, scope: &Scope) -> Result<String, BagError> {
+    let included: HashSet<Color> = match scope {
+        Scope::All => graph.colors().into_iter().cloned().collect(),
+        Scope::From(color) => {
+            let mut included = graph.descendants(color)?;
+            included.insert(color.clone());
+            included
+        }
+        Scope::To(color) => {
+            let mut included = graph.containers(color)?;
+            included.insert(color.clone());
+            included
+        }
+    };
+
+    let colors: Vec<&Color> = graph.colors().into_iter()
+        .filter(|&color| included.contains(color))
+        .collect();
+
+    // Edges between included colors only, grouped by outer color and sorted by inner color
+    let edges: Vec<(&Color, Vec<&Content>)> = colors.iter()
+        .map(|&outer| (
+            outer,
+            graph.contents(outer).unwrap().iter()
+                .filter(|content| included.contains(&content.color))
+                .collect()
+        ))
+        .collect();
+
+    let mut result = String::new();
+
+    match format {
+        Format::Dot => {
+            result.push_str("digraph bags {\n");
+
+            for color in &colors {
+                result.push_str(&format!("    {0};\n", quote(color)));
+            }
+
+            for (outer, contents) in &edges {
+                for content in contents {
+                    result.push_str(&format!("    {0} -> {1} [label=\"{2}\"];\n", quote(outer), quote(&content.color), content.count));
+                }
+            }
+
+            result.push_str("}\n");
+        }
+        Format::Json => {
+            let entries: Vec<String> = edges.iter()
+                .map(|(outer, contents)| {
+                    let contents: Vec<String> = contents.iter()
+                        .map(|content| format!("{0}: {1}", quote(&content.color), content.count))
+                        .collect();
+
+                    format!("  {0}: {{{1}}}", quote(outer), contents.join(", "))
+                })
+                .collect();
+
+            result.push_str(&format!("{{\n{0}\n}}\n", entries.join(",\n")));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Quotes a color as a string literal valid in both DOT and JSON.
+fn quote(color: &str) -> String {
+    format!("\"{0}\"", color.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Bags;
+
+    fn graph() -> BagGraph {
+        let bags: Bags = vec![
+            ("light red", vec![(1, "bright white"), (2, "muted yellow")]),
+            ("bright white", vec![(3, "shiny gold")]),
+            ("muted yellow", vec![]),
+            ("shiny gold", vec![]),
+        ].into_iter()
+            .map(|(color, contents)| (
+                color.to_string(),
+                contents.into_iter()
+                    .map(|(count, color)| Content { color: color.to_string(), count })
+                    .collect()
+            ))
+            .collect();
+
+        BagGraph::from(&bags)
+    }
+
+    #[test]
+    fn export_dot_labels_edges_with_counts() {
+        let dot = export(&graph(), &Format::Dot, &Scope::All).unwrap();
+
+        assert_eq!(dot, r#"digraph bags {
+    "bright white";
+    "light red";
+    "muted yellow";
+    "shiny gold";
+    "bright white" -> "shiny gold" [label="3"];
+    "light red" -> "bright white" [label="1"];
+    "light red" -> "muted yellow" [label="2"];
+}
+"#);
+    }
+
+    #[test]
+    fn export_json_from_color() {
+        let json = export(&graph(), &Format::Json, &Scope::From(String::from("bright white"))).unwrap();
+
+        assert_eq!(json, r#"{
+  "bright white": {"shiny gold": 3},
+  "shiny gold": {}
+}
+"#);
+    }
+
+    #[test]
+    fn export_dot_to_color() {
+        let dot = export(&graph(), &Format::Dot, &Scope::To(String::from("muted yellow"))).unwrap();
+
+        assert_eq!(dot, r#"digraph bags {
+    "light red";
+    "muted yellow";
+    "light red" -> "muted yellow" [label="2"];
+}
+"#);
+    }
+
+    #[test]
+    fn export_unknown_color_is_error() {
+        assert!(export(&graph(), &Format::Json, &Scope::To(String::from("plaid purple"))).is_err());
+    }
+
+    #[test]
+    fn quote_escapes_quotes_and_backslashes() {
+        assert_eq!(quote(r#"odd "blue\green""#), r#""odd \"blue\\green\"""#);
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+pub type Color = String;
+pub type Contents = HashSet<Content>;
+pub type Bags = HashMap<Color, Contents>;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Content {
+    pub color: Color,
+    pub count: usize,
+}
+
+/// A cycle in the bag rules; the path starts and ends with the same color.
+#[derive(Debug, PartialEq)]
+pub struct Cycle(pub Vec<Color>);
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0}", self.0.join(" -> "))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BagError {
+    UnknownColor(Color),
+    Cycle(Cycle),
+    /// Counting the bags inside the given color does not fit into a `usize`.
+    Overflow(Color),
+}
+
+impl fmt::Display for BagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BagError::UnknownColor(color) => write!(f, "Unknown bag color: {0}", color),
+            BagError::Cycle(cycle) => write!(f, "Bag rules contain a cycle: {0}", cycle),
+            BagError::Overflow(color) => write!(f, "Too many bags inside {0} to count", color),
+        }
+    }
+}
+
+/// The bag rules as a directed graph: an edge `outer -> inner` means `outer` directly contains `inner`.
+///
+/// Colors that only ever appear as contents are part of the graph as well (without any contents of their own).
+pub struct BagGraph {
+    forward: HashMap<Color, Vec<Content>>,
+    reverse: HashMap<Color, HashSet<Color>>,
+}
+
+impl BagGraph {
+    pub fn from(bags: &Bags) -> Self {
+        let mut forward: HashMap<Color, Vec<Content>> = HashMap::new();
+        let mut reverse: HashMap<Color, HashSet<Color>> = HashMap::new();
+
+        for (color, contents) in bags {
+            forward.entry(color.clone()).or_default();
+            reverse.entry(color.clone()).or_default();
+
+            for content in contents {
+                forward.entry(content.color.clone()).or_default();
+                reverse.entry(content.color.clone()).or_default()
+                    .insert(color.clone());
+            }
+
+            let mut contents: Vec<Content> = contents.iter().cloned().collect();
+            contents.sort_by(|a, b| a.color.cmp(&b.color));
+
+            forward.insert(color.clone(), contents);
+        }
+
+        Self {
+            forward,
+            reverse,
+        }
+    }
+
+    pub fn contains(&self, color: &str) -> bool {
+        self.forward.contains_key(color)
+    }
+
+    fn check_known(&self, color: &str) -> Result<(), BagError> {
+        if self.contains(color) {
+            Ok(())
+        } else {
+            Err(BagError::UnknownColor(color.to_string()))
+        }
+    }
+
+    /// All colors in alphabetical order.
+    pub fn colors(&self) -> Vec<&Color> {
+        let mut colors: Vec<&Color> = self.forward.keys().collect();
+        colors.sort_unstable();
+
+        colors
+    }
+
+    /// The bags directly inside a bag of the given color.
+    pub fn contents(&self, color: &str) -> Result<&[Content], BagError> {
+        self.forward.get(color)
+            .map(Vec::as_slice)
+            .ok_or_else(|| BagError::UnknownColor(color.to_string()))
+    }
+
+    /// All colors that (transitively) contain at least one bag of the given color.
+    pub fn containers(&self, color: &str) -> Result<HashSet<Color>, BagError> {
+        self.check_known(color)?;
+
+        let mut can_contain: HashSet<Color> = HashSet::new();
+        let mut might_contain: VecDeque<&Color> = VecDeque::new();
+
+        might_contain.extend(&self.reverse[color]);
+
+        while let Some(color) = might_contain.pop_front() {
+            if can_contain.insert(color.clone()) {
+                might_contain.extend(&self.reverse[color]);
+            }
+        }
+
+        Ok(can_contain)
+    }
+
+    /// All colors (transitively) contained in a bag of the given color.
+    pub fn descendants(&self, color: &str) -> Result<HashSet<Color>, BagError> {
+        let mut contained: HashSet<Color> = HashSet::new();
+        let mut pending: Vec<&Content> = self.contents(color)?.iter().collect();
+
+        while let Some(content) = pending.pop() {
+            if contained.insert(content.color.clone()) {
+                pending.extend(&self.forward[&content.color]);
+            }
+        }
+
+        Ok(contained)
+    }
+
+    /// The total number of bags inside a bag of the given color.
+    pub fn total_bags_inside(&self, color: &str) -> Result<usize, BagError> {
+        self.check_known(color)?;
+
+        self.count_bags(color, &mut HashMap::new(), &mut Vec::new())
+    }
+
+    /// Counts bottom-up with a per-color cache, so shared sub-bags are only counted once.
+    /// `path` holds the colors currently being counted and is used to detect cycles.
+    fn count_bags<'a>(&'a self, color: &'a str, cache: &mut HashMap<&'a str, usize>, path: &mut Vec<&'a str>) -> Result<usize, BagError> {
+        if let Some(&sum) = cache.get(color) {
+            return Ok(sum);
+        }
+
+        if let Some(cycle) = cycle_through(path, color) {
+            return Err(BagError::Cycle(cycle));
+        }
+
+        path.push(color);
+
+        let mut sum: usize = 0;
+
+        for inner_bag in &self.forward[color] {
+            let inside = self.count_bags(&inner_bag.color, cache, path)?;
+
+            // Each inner bag counts itself plus everything inside it
+            sum = inside.checked_add(1)
+                .and_then(|bags| bags.checked_mul(inner_bag.count))
+                .and_then(|bags| bags.checked_add(sum))
+                .ok_or_else(|| BagError::Overflow(color.to_string()))?;
+        }
+
+        path.pop();
+        cache.insert(color, sum);
+
+        Ok(sum)
+    }
+
+    /// All colors ordered so that every bag comes after all bags it contains (innermost bags first).
+    ///
+    /// Ties are broken alphabetically, so the order is stable across runs.
+    pub fn topological_order(&self) -> Result<Vec<Color>, Cycle> {
+        let colors = self.colors();
+
+        let mut order: Vec<Color> = Vec::with_capacity(colors.len());
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut path: Vec<&str> = Vec::new();
+
+        for color in colors {
+            self.visit(color.as_str(), &mut done, &mut path, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit<'a>(&'a self, color: &'a str, done: &mut HashSet<&'a str>, path: &mut Vec<&'a str>, order: &mut Vec<Color>) -> Result<(), Cycle> {
+        if done.contains(color) {
+            return Ok(());
+        }
+
+        if let Some(cycle) = cycle_through(path, color) {
+            return Err(cycle);
+        }
+
+        path.push(color);
+
+        for inner_bag in &self.forward[color] {
+            self.visit(&inner_bag.color, done, path, order)?;
+        }
+
+        path.pop();
+        done.insert(color);
+        order.push(color.to_string());
+
+        Ok(())
+    }
+
+    /// Whether a bag of color `from` (transitively) contains a bag of color `to`.
+    pub fn can_reach(&self, from: &str, to: &str) -> Result<bool, BagError> {
+        self.check_known(to)?;
+
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut pending: Vec<&str> = self.contents(from)?.iter()
+            .map(|content| content.color.as_str())
+            .collect();
+
+        while let Some(color) = pending.pop() {
+            if color == to {
+                return Ok(true);
+            }
+
+            if visited.insert(color) {
+                pending.extend(self.forward[color].iter().map(|content| content.color.as_str()));
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// If `color` is already on the current DFS `path`, returns the cycle from its first occurrence back to itself.
+fn cycle_through(path: &[&str], color: &str) -> Option<Cycle> {
+    path.iter()
+        .position(|&on_path| on_path == color)
+        .map(|start| Cycle(path[start..].iter()
+            .chain(std::iter::once(&color))
+            .map(|color| color.to_string())
+            .collect()
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bags(rules: &[(&str, &[(usize, &str)])]) -> Bags {
+        rules.iter()
+            .map(|(color, contents)| (
+                color.to_string(),
+                contents.iter()
+                    .map(|(count, color)| Content { color: color.to_string(), count: *count })
+                    .collect()
+            ))
+            .collect()
+    }
+
+    fn example1_graph() -> BagGraph {
+        BagGraph::from(&bags(&[
+            ("light red", &[(1, "bright white"), (2, "muted yellow")]),
+            ("dark orange", &[(3, "bright white"), (4, "muted yellow")]),
+            ("bright white", &[(1, "shiny gold")]),
+            ("muted yellow", &[(2, "shiny gold"), (9, "faded blue")]),
+            ("shiny gold", &[(1, "dark olive"), (2, "vibrant plum")]),
+            ("dark olive", &[(3, "faded blue"), (4, "dotted black")]),
+            ("vibrant plum", &[(5, "faded blue"), (6, "dotted black")]),
+            ("faded blue", &[]),
+            ("dotted black", &[]),
+        ]))
+    }
+
+    #[test]
+    fn containers_example1() {
+        let containers = example1_graph().containers("shiny gold").unwrap();
+
+        let mut containers: Vec<Color> = containers.into_iter().collect();
+        containers.sort();
+
+        assert_eq!(containers, vec!["bright white", "dark orange", "light red", "muted yellow"]);
+    }
+
+    #[test]
+    fn total_bags_inside_example1() {
+        assert_eq!(example1_graph().total_bags_inside("shiny gold"), Ok(32));
+    }
+
+    /// Every level holds one of each of the two bags of the next level, so the naive count has 2^n paths.
+    fn doubling_graph(levels: usize) -> BagGraph {
+        let rules: Vec<(String, Vec<(usize, String)>)> = (0..levels)
+            .flat_map(|level| vec!["left", "right"].into_iter()
+                .map(move |side| (
+                    format!("{0} {1}", side, level),
+                    vec![(1, format!("left {0}", level + 1)), (1, format!("right {0}", level + 1))],
+                ))
+            )
+            .collect();
+
+        let bags: Bags = rules.into_iter()
+            .map(|(color, contents)| (
+                color,
+                contents.into_iter()
+                    .map(|(count, color)| Content { color, count })
+                    .collect()
+            ))
+            .collect();
+
+        BagGraph::from(&bags)
+    }
+
+    #[test]
+    fn total_bags_inside_is_memoized() {
+        let graph = doubling_graph(60);
+
+        assert_eq!(graph.total_bags_inside("left 0"), Ok((1 << 61) - 2));
+    }
+
+    #[test]
+    fn total_bags_inside_reports_overflow() {
+        let graph = doubling_graph(70);
+
+        assert!(matches!(graph.total_bags_inside("left 0"), Err(BagError::Overflow(_))));
+    }
+
+    #[test]
+    fn total_bags_inside_reports_cycle() {
+        let graph = BagGraph::from(&bags(&[
+            ("light red", &[(1, "bright white")]),
+            ("bright white", &[(2, "muted yellow")]),
+            ("muted yellow", &[(3, "bright white")]),
+        ]));
+
+        let expected: Vec<Color> = vec!["bright white", "muted yellow", "bright white"].into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(graph.total_bags_inside("light red"), Err(BagError::Cycle(Cycle(expected))));
+    }
+
+    #[test]
+    fn topological_order_puts_innermost_bags_first() {
+        let graph = example1_graph();
+        let order = graph.topological_order().unwrap();
+
+        assert_eq!(order.len(), 9);
+
+        let position = |color: &str| order.iter().position(|c| c == color).unwrap();
+
+        for color in &order {
+            for inner_bag in graph.contents(color).unwrap() {
+                assert!(position(&inner_bag.color) < position(color), "{0} before {1}", inner_bag.color, color);
+            }
+        }
+    }
+
+    #[test]
+    fn topological_order_reports_cycle() {
+        let graph = BagGraph::from(&bags(&[
+            ("bright white", &[(2, "muted yellow")]),
+            ("muted yellow", &[(3, "bright white")]),
+        ]));
+
+        let expected: Vec<Color> = vec!["bright white", "muted yellow", "bright white"].into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(graph.topological_order(), Err(Cycle(expected)));
+    }
+
+    #[test]
+    fn descendants_example1() {
+        let descendants = example1_graph().descendants("shiny gold").unwrap();
+
+        let mut descendants: Vec<Color> = descendants.into_iter().collect();
+        descendants.sort();
+
+        assert_eq!(descendants, vec!["dark olive", "dotted black", "faded blue", "vibrant plum"]);
+    }
+
+    #[test]
+    fn contents_are_sorted_by_color() {
+        let graph = example1_graph();
+
+        let colors: Vec<&str> = graph.contents("muted yellow").unwrap().iter()
+            .map(|content| content.color.as_str())
+            .collect();
+
+        assert_eq!(colors, vec!["faded blue", "shiny gold"]);
+    }
+
+    #[test]
+    fn can_reach_follows_edges_downwards_only() {
+        let graph = example1_graph();
+
+        assert_eq!(graph.can_reach("light red", "dotted black"), Ok(true));
+        assert_eq!(graph.can_reach("dotted black", "light red"), Ok(false));
+        assert_eq!(graph.can_reach("shiny gold", "shiny gold"), Ok(false));
+    }
+
+    #[test]
+    fn unknown_colors_are_errors() {
+        let graph = example1_graph();
+
+        assert_eq!(graph.containers("plaid purple"), Err(BagError::UnknownColor(String::from("plaid purple"))));
+        assert!(graph.total_bags_inside("plaid purple").is_err());
+        assert!(graph.can_reach("shiny gold", "plaid purple").is_err());
+    }
+
+    #[test]
+    fn colors_only_used_as_contents_are_known() {
+        let graph = BagGraph::from(&bags(&[("light red", &[(1, "bright white")])]));
+
+        assert_eq!(graph.total_bags_inside("bright white"), Ok(0));
+        assert_eq!(graph.containers("bright white").unwrap().len(), 1);
+    }
+}
@@ -1,114 +1,144 @@
-use std::cmp::max;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::io::{BufRead, stdin};
-use std::iter::FromIterator;
-use std::ops::{Add, Deref};
+use std::process;
 
-use regex::Regex;
+use crate::export::{export, Format, Scope};
+use crate::graph::{BagError, BagGraph, Bags, Color, Contents};
+use crate::parser::Rule;
+use crate::validate::{RuleIssue, validate};
 
-type Color = String;
-type Contents = HashSet<Content>;
-type Bags = HashMap<Color, Contents>;
-
-#[derive(Debug, Eq, Hash, Clone)]
-struct Content {
-    color: Color,
-    count: usize,
-}
-
-impl PartialEq for Content {
-    fn eq(&self, other: &Self) -> bool {
-        self.color == other.color
-            && self.count == other.count
-    }
-}
+mod export;
+mod graph;
+mod parser;
+mod validate;
 
 fn main() {
     let bag_rules: Vec<String> = stdin().lock().lines()
         .map(|maybe_line| maybe_line.expect("Error while reading line"))
         .collect();
 
-    let bag_contents_re = Regex::new(r"([0-9]+) ([a-zA-z ]+) bags?[.,]?")
-        .expect("Could not compile regex");
+    let parsed_rules: Vec<Rule> = bag_rules.iter()
+        .enumerate()
+        .map(|(index, line)| line.parse::<Rule>()
+            .unwrap_or_else(|error| {
+                eprintln!("line {0}, {1}: {2}", index + 1, error, line);
+                process::exit(1);
+            })
+        )
+        .collect();
 
-    let bags: Bags = bag_rules.into_iter()
-        .map(|line| split_rule(&line, &bag_contents_re))
+    let rules: Vec<(Color, Contents)> = parsed_rules.iter()
+        .map(|rule| (rule.color.clone(), rule.to_contents()))
         .collect();
 
-    println!("bags = {0:?}", bags);
+    let bags: Bags = rules.iter().cloned().collect();
 
-    let shiny_gold_name = Color::from("shiny gold");
+    let graph = BagGraph::from(&bags);
 
-    let mut reverse_bags: HashMap<Color, HashSet<Color>> = HashMap::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
 
-    for (color, contents) in bags.clone().into_iter() {
-        contents.into_iter()
-            .for_each(|c| {
-                (*reverse_bags.entry(c.color).or_insert(HashSet::new())).insert(color.to_string());
-            });
-    }
+    let result = match args.as_slice() {
+        [] => {
+            println!("bags = {0:?}", bags);
+            solve(&graph, "shiny gold")
+        }
+        ["containers", color] => graph.containers(color)
+            .map(|containers| {
+                let mut containers: Vec<Color> = containers.into_iter().collect();
+                containers.sort();
+
+                println!("containers = {0} -> {1:?}", containers.len(), containers);
+            }),
+        ["count", color] => graph.total_bags_inside(color)
+            .map(|sum_bags| println!("sum_bags = {0}", sum_bags)),
+        ["contents", color] => graph.contents(color)
+            .map(|contents| {
+                for content in contents {
+                    println!("{0} {1}", content.count, content.color);
+                }
+            }),
+        ["reaches", from, to] => graph.can_reach(from, to)
+            .map(|reaches| println!("{0} -> {1}: {2}", from, to, reaches)),
+        ["validate"] => {
+            let validation = validate(&rules);
+
+            for issue in &validation.issues {
+                println!("{0}", issue);
+            }
 
-    // All bags that can contain "shiny gold" directly
-    let mut can_contain: HashSet<Color> = reverse_bags.get(&shiny_gold_name).unwrap().clone();
+            println!("{0} rules, {1} issue(s)", rules.len(), validation.issues.len());
 
-    // A deque that holds bags that contain bags that contain "shiny gold" in some way
-    let mut might_contain: VecDeque<Color> = VecDeque::from_iter(can_contain.clone());
+            if !validation.issues.is_empty() {
+                process::exit(1);
+            }
+
+            Ok(())
+        }
+        ["order"] => {
+            let validation = validate(&rules);
 
-    while let Some(color) = might_contain.pop_front() {
-        if let Some(contents) = reverse_bags.get(&color) {
-            // println!("can_contain = {0:?}", can_contain);
-            // println!("might_contain = {0:?}", might_contain);
-            // println!("trying {0} = {1:?}", color, contents);
+            match validation.order {
+                Some(order) => {
+                    for color in order {
+                        println!("{0}", color);
+                    }
 
-            for x in contents {
-                if !can_contain.contains(x) {
-                    might_contain.push_back(x.clone());
-                    can_contain.insert(x.clone());
+                    Ok(())
                 }
+                None => Err(validation.issues.into_iter()
+                    .find_map(|issue| match issue {
+                        RuleIssue::Cycle(cycle) => Some(BagError::Cycle(cycle)),
+                        _ => None,
+                    })
+                    .expect("Rules without an order have a cycle")
+                ),
             }
         }
-    }
-
-    println!("can_contain = {0} -> {1:?}", can_contain.len(), can_contain);
+        ["canonical"] => {
+            for rule in &parsed_rules {
+                println!("{0}", rule);
+            }
 
-    let mut sum_bags = count_bags(&bags, &shiny_gold_name);
+            Ok(())
+        }
+        ["export", format, scope @ ..] => {
+            let format = format.parse::<Format>()
+                .unwrap_or_else(|error| exit_with_usage(&error));
+
+            let scope = match scope {
+                [] => Scope::All,
+                ["--from", color] => Scope::From(color.to_string()),
+                ["--to", color] => Scope::To(color.to_string()),
+                _ => exit_with_usage("Expected --from <color> or --to <color>"),
+            };
+
+            export(&graph, &format, &scope)
+                .map(|exported| print!("{0}", exported))
+        }
+        _ => exit_with_usage("Unknown command"),
+    };
 
-    println!("sum_bags = {0}", sum_bags);
+    if let Err(error) = result {
+        eprintln!("{0}", error);
+        process::exit(1);
+    }
 }
 
-fn split_rule(line: &str, re: &Regex) -> (Color, Contents) {
-    let mut tokens = line.split(" bags contain ");
-
-    (
-        String::from(tokens.next()
-            .expect(String::from("Could not extract bag color from: ").add(&line).as_str())
-        ),
-        tokens.next()
-            .map(|contents| split_contents(&contents, &re))
-            .expect(String::from("Could not extract bag contents from: ").add(&line).as_str()),
-    )
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day07 [containers <color>|count <color>|contents <color>|reaches <from> <to>|validate|order|canonical|export dot|json [--from <color>|--to <color>]] < input");
+    process::exit(1);
 }
 
-fn split_contents(line: &str, re: &Regex) -> Contents {
-    re.captures_iter(line.trim())
-        // .map(|capture| {
-        //     println!("captured = {0:?}", capture);
-        //     capture
-        // })
-        .map(|capture| (capture.get(1).unwrap(), capture.get(2).unwrap()))
-        .map(|(capacity, color)| Content {
-            color: color.as_str().to_string(),
-            count: capacity.as_str().parse::<usize>().unwrap(),
-        })
-        .collect()
-}
+fn solve(graph: &BagGraph, color: &str) -> Result<(), BagError> {
+    let can_contain = graph.containers(color)?;
+
+    println!("can_contain = {0} -> {1:?}", can_contain.len(), can_contain);
 
-fn count_bags(bags: &Bags, color: &String) -> usize {
-    let mut sum = 0;
+    let sum_bags = graph.total_bags_inside(color)?;
 
-    for inner_bag in bags.get(color).unwrap() {
-        sum += inner_bag.count + inner_bag.count * count_bags(bags, &inner_bag.color);
-    }
+    println!("sum_bags = {0}", sum_bags);
 
-    sum
+    Ok(())
 }
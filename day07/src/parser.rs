@@ -0,0 +1,351 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::graph::{Color, Content, Contents};
+
+/// A single rule sentence, e.g. `light red bags contain 1 bright white bag, 2 muted yellow bags.`
+///
+/// Grammar (tokens separated by whitespace):
+///
+/// ```text
+/// rule     := color bag "contain" contents ["."]
+/// contents := "no" "other" bag | item ("," item)*
+/// item     := count color bag
+/// color    := word+
+/// bag      := "bag" | "bags"
+/// ```
+///
+/// `Display` prints the canonical form: singular/plural "bag(s)" matching the count, single spaces and a trailing period.
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub color: Color,
+    /// In the order they appear in the rule
+    pub contents: Vec<Content>,
+}
+
+impl Rule {
+    pub fn to_contents(&self) -> Contents {
+        self.contents.iter().cloned().collect()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0} bags contain ", self.color)?;
+
+        if self.contents.is_empty() {
+            return write!(f, "no other bags.");
+        }
+
+        let contents: Vec<String> = self.contents.iter()
+            .map(|content| format!("{0} {1} {2}", content.count, content.color, if content.count == 1 { "bag" } else { "bags" }))
+            .collect();
+
+        write!(f, "{0}.", contents.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// 1-based character column the error refers to
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {0}: {1}", self.column, self.message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TokenKind<'a> {
+    Word(&'a str),
+    Number(usize),
+    Comma,
+    Period,
+}
+
+impl fmt::Display for TokenKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Word(word) => write!(f, "'{0}'", word),
+            TokenKind::Number(number) => write!(f, "{0}", number),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Period => write!(f, "'.'"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    column: usize,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    // Number of chars consumed so far, so the current char is at column `consumed + 1`
+    let mut consumed = 0;
+
+    while let Some(&(start, char)) = chars.peek() {
+        let column = consumed + 1;
+
+        if char.is_whitespace() {
+            chars.next();
+            consumed += 1;
+            continue;
+        }
+
+        if char == ',' || char == '.' {
+            chars.next();
+            consumed += 1;
+            tokens.push(Token { kind: if char == ',' { TokenKind::Comma } else { TokenKind::Period }, column });
+            continue;
+        }
+
+        if !char.is_ascii_alphanumeric() {
+            return Err(ParseError { column, message: format!("unexpected character {0:?}", char) });
+        }
+
+        let mut end = start;
+
+        while let Some(&(index, char)) = chars.peek() {
+            if !char.is_ascii_alphanumeric() {
+                break;
+            }
+
+            end = index + char.len_utf8();
+            chars.next();
+            consumed += 1;
+        }
+
+        let text = &s[start..end];
+
+        let kind = if text.chars().all(|char| char.is_ascii_digit()) {
+            TokenKind::Number(text.parse::<usize>()
+                .map_err(|_| ParseError { column, message: format!("count {0} is too large", text) })?
+            )
+        } else if text.chars().all(|char| char.is_ascii_lowercase()) {
+            TokenKind::Word(text)
+        } else {
+            return Err(ParseError { column, message: format!("invalid word '{0}' (expected lowercase letters or a number)", text) });
+        };
+
+        tokens.push(Token { kind, column });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    // Column just after the end of the input, used for errors at the end of the rule
+    end_column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&TokenKind<'a>> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.position)
+            .map(|token| token.column)
+            .unwrap_or(self.end_column)
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, ParseError> {
+        let found = match self.peek() {
+            Some(kind) => kind.to_string(),
+            None => String::from("end of rule"),
+        };
+
+        Err(ParseError { column: self.column(), message: format!("expected {0}, found {1}", expected, found) })
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(TokenKind::Word(found)) if *found == word => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => self.error(&format!("'{0}'", word)),
+        }
+    }
+
+    fn is_bag(kind: Option<&TokenKind<'_>>) -> bool {
+        matches!(kind, Some(TokenKind::Word("bag")) | Some(TokenKind::Word("bags")))
+    }
+
+    fn expect_bag(&mut self) -> Result<(), ParseError> {
+        if Self::is_bag(self.peek()) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error("'bag' or 'bags'")
+        }
+    }
+
+    fn color(&mut self) -> Result<Color, ParseError> {
+        let mut words: Vec<&str> = Vec::new();
+
+        while !Self::is_bag(self.peek()) {
+            match self.peek() {
+                Some(TokenKind::Word(word)) => {
+                    words.push(word);
+                    self.position += 1;
+                }
+                _ if words.is_empty() => return self.error("a color"),
+                _ => return self.error("'bag' or 'bags'"),
+            }
+        }
+
+        if words.is_empty() {
+            return self.error("a color");
+        }
+
+        Ok(words.join(" "))
+    }
+
+    fn rule(&mut self) -> Result<Rule, ParseError> {
+        let color = self.color()?;
+        self.expect_bag()?;
+        self.expect_word("contain")?;
+
+        let mut contents = Vec::new();
+
+        if self.peek() == Some(&TokenKind::Word("no")) {
+            self.position += 1;
+            self.expect_word("other")?;
+            self.expect_bag()?;
+        } else {
+            let mut seen: HashSet<Color> = HashSet::new();
+
+            loop {
+                let count = match self.peek() {
+                    Some(TokenKind::Number(0)) =>
+                        return Err(ParseError { column: self.column(), message: String::from("count must be positive (use 'no other bags' for empty bags)") }),
+                    Some(TokenKind::Number(count)) => *count,
+                    _ => return self.error("a count or 'no other bags'"),
+                };
+                self.position += 1;
+
+                let column = self.column();
+                let color = self.color()?;
+                self.expect_bag()?;
+
+                if !seen.insert(color.clone()) {
+                    return Err(ParseError { column, message: format!("{0} is listed more than once", color) });
+                }
+
+                contents.push(Content { color, count });
+
+                if self.peek() == Some(&TokenKind::Comma) {
+                    self.position += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if self.peek() == Some(&TokenKind::Period) {
+            self.position += 1;
+        }
+
+        if self.peek().is_some() {
+            return self.error("end of rule");
+        }
+
+        Ok(Rule {
+            color,
+            contents,
+        })
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+            end_column: s.chars().count() + 1,
+        };
+
+        parser.rule()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(count: usize, color: &str) -> Content {
+        Content { color: String::from(color), count }
+    }
+
+    fn error(column: usize, message: &str) -> Result<Rule, ParseError> {
+        Err(ParseError { column, message: String::from(message) })
+    }
+
+    #[test]
+    fn parses_plural_and_singular_contents() {
+        let rule = "light red bags contain 1 bright white bag, 2 muted yellow bags.".parse::<Rule>();
+
+        assert_eq!(rule, Ok(Rule {
+            color: String::from("light red"),
+            contents: vec![content(1, "bright white"), content(2, "muted yellow")],
+        }));
+    }
+
+    #[test]
+    fn parses_no_other_bags() {
+        let rule = "faded blue bags contain no other bags.".parse::<Rule>();
+
+        assert_eq!(rule, Ok(Rule { color: String::from("faded blue"), contents: vec![] }));
+    }
+
+    #[test]
+    fn trailing_period_and_extra_whitespace_are_optional() {
+        let rule = "  bright  white bags contain 1 shiny gold bag ".parse::<Rule>().unwrap();
+
+        assert_eq!(rule.to_string(), "bright white bags contain 1 shiny gold bag.");
+    }
+
+    #[test]
+    fn display_fixes_bag_plurals() {
+        let rule = "shiny gold bag contain 1 dark olive bags, 2 vibrant plum bag".parse::<Rule>().unwrap();
+
+        assert_eq!(rule.to_string(), "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.");
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert_eq!("light red bags hold 1 bright white bag.".parse::<Rule>(), error(16, "expected 'contain', found 'hold'"));
+        assert_eq!("light red bags contain bright white bags.".parse::<Rule>(), error(24, "expected a count or 'no other bags', found 'bright'"));
+        assert_eq!("light red bags contain 1 bright white.".parse::<Rule>(), error(38, "expected 'bag' or 'bags', found '.'"));
+        assert_eq!("light red bags contain 0 bright white bags.".parse::<Rule>(), error(24, "count must be positive (use 'no other bags' for empty bags)"));
+        assert_eq!("light red bags contain no bags.".parse::<Rule>(), error(27, "expected 'other', found 'bags'"));
+        assert_eq!("light red bags contain no other bags. extra".parse::<Rule>(), error(39, "expected end of rule, found 'extra'"));
+        assert_eq!("light red bags contain 1 red bag, 2 red bags.".parse::<Rule>(), error(37, "red is listed more than once"));
+        assert_eq!("bags contain no other bags.".parse::<Rule>(), error(1, "expected a color, found 'bags'"));
+        assert_eq!("light red bags contain".parse::<Rule>(), error(23, "expected a count or 'no other bags', found end of rule"));
+        assert_eq!("Light red bags contain no other bags.".parse::<Rule>(), error(1, "invalid word 'Light' (expected lowercase letters or a number)"));
+        assert_eq!("light red bags contain 1 bright-white bag.".parse::<Rule>(), error(32, "unexpected character '-'"));
+    }
+
+    #[test]
+    fn round_trips_bundled_input() {
+        for line in include_str!("../resources/input").lines() {
+            let rule = line.parse::<Rule>()
+                .unwrap_or_else(|error| panic!("{0}: {1}", error, line));
+
+            assert_eq!(rule.to_string(), line);
+        }
+    }
+}
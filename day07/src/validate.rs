@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use crate::graph::{BagGraph, Bags, Color, Contents, Cycle};
+
+#[derive(Debug, PartialEq)]
+pub enum RuleIssue {
+    /// More than one rule line for the same color (1-based line numbers)
+    DuplicateRule { color: Color, lines: Vec<usize> },
+    /// A color used as contents that has no rule of its own
+    UndefinedColor { color: Color, referenced_by: Vec<Color> },
+    /// The rules contain a cycle; the path starts and ends with the same color
+    Cycle(Cycle),
+}
+
+impl fmt::Display for RuleIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleIssue::DuplicateRule { color, lines } => {
+                let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+                write!(f, "duplicate rules for {0} on lines {1}", color, lines.join(", "))
+            }
+            RuleIssue::UndefinedColor { color, referenced_by } =>
+                write!(f, "{0} is never defined but contained in: {1}", color, referenced_by.join(", ")),
+            RuleIssue::Cycle(cycle) => write!(f, "cycle: {0}", cycle),
+        }
+    }
+}
+
+/// The result of validating a rule file.
+pub struct Validation {
+    pub issues: Vec<RuleIssue>,
+    /// Innermost bags first; only available if the rules are acyclic.
+    pub order: Option<Vec<Color>>,
+}
+
+/// Checks the rules (in input order, one per parsed `Rule`) for duplicates, undefined colors and cycles.
+pub fn validate(rules: &[(Color, Contents)]) -> Validation {
+    let mut issues = Vec::new();
+
+    let mut rule_lines: BTreeMap<&Color, Vec<usize>> = BTreeMap::new();
+
+    for (index, (color, _contents)) in rules.iter().enumerate() {
+        rule_lines.entry(color).or_default().push(index + 1);
+    }
+
+    for (color, lines) in &rule_lines {
+        if lines.len() > 1 {
+            issues.push(RuleIssue::DuplicateRule { color: color.to_string(), lines: lines.clone() });
+        }
+    }
+
+    let mut undefined: BTreeMap<&Color, BTreeSet<&Color>> = BTreeMap::new();
+
+    for (color, contents) in rules {
+        for content in contents {
+            if !rule_lines.contains_key(&content.color) {
+                undefined.entry(&content.color).or_default().insert(color);
+            }
+        }
+    }
+
+    for (color, referenced_by) in undefined {
+        issues.push(RuleIssue::UndefinedColor {
+            color: color.to_string(),
+            referenced_by: referenced_by.into_iter().cloned().collect(),
+        });
+    }
+
+    // Later duplicates win, the same as collecting into `Bags` directly
+    let bags: Bags = rules.iter().cloned().collect::<HashMap<_, _>>();
+
+    let order = match BagGraph::from(&bags).topological_order() {
+        Ok(order) => Some(order),
+        Err(cycle) => {
+            issues.push(RuleIssue::Cycle(cycle));
+            None
+        }
+    };
+
+    Validation {
+        issues,
+        order,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Content;
+
+    fn rules(rules: &[(&str, &[(usize, &str)])]) -> Vec<(Color, Contents)> {
+        rules.iter()
+            .map(|(color, contents)| (
+                color.to_string(),
+                contents.iter()
+                    .map(|(count, color)| Content { color: color.to_string(), count: *count })
+                    .collect()
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn validate_accepts_consistent_rules() {
+        let validation = validate(&rules(&[
+            ("light red", &[(1, "bright white")]),
+            ("bright white", &[]),
+        ]));
+
+        assert_eq!(validation.issues, vec![]);
+        assert_eq!(validation.order, Some(vec![String::from("bright white"), String::from("light red")]));
+    }
+
+    #[test]
+    fn validate_reports_duplicates_and_undefined_colors() {
+        let validation = validate(&rules(&[
+            ("light red", &[(1, "bright white")]),
+            ("dark orange", &[(2, "bright white")]),
+            ("light red", &[]),
+        ]));
+
+        assert_eq!(validation.issues, vec![
+            RuleIssue::DuplicateRule { color: String::from("light red"), lines: vec![1, 3] },
+            RuleIssue::UndefinedColor {
+                color: String::from("bright white"),
+                referenced_by: vec![String::from("dark orange"), String::from("light red")],
+            },
+        ]);
+        assert!(validation.order.is_some());
+    }
+
+    #[test]
+    fn validate_reports_cycle_without_order() {
+        let validation = validate(&rules(&[
+            ("light red", &[(1, "light red")]),
+        ]));
+
+        assert_eq!(validation.issues, vec![
+            RuleIssue::Cycle(Cycle(vec![String::from("light red"), String::from("light red")])),
+        ]);
+        assert_eq!(validation.order, None);
+    }
+}
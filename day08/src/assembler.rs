@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::{Instruction, InstructionDelta, InstructionIndex, InstructionTable, Operand, Register};
+use crate::OpCode::{ACC, ADD, HALT, JMP, JNZ, JZ, MUL, NOP, SET};
+
+type Labels = HashMap<String, InstructionIndex>;
+
+/// Assembles a program, one instruction per line.
+///
+/// ```text
+/// line        := [label ":"] [instruction] [comment]
+/// comment     := ("#" | ";") any text
+/// instruction := ("nop" | "acc" | "jmp") offset
+///              | ("add" | "mul" | "set") register (value | register)
+///              | ("jz" | "jnz") register offset
+///              | "halt"
+/// offset      := signed number | label
+/// ```
+///
+/// Jumps to a label are resolved to the relative offset of the instruction following it. Blank lines, comments and
+/// lines with only a label produce no instruction. Errors are prefixed with the 1-based line number.
+pub fn assemble(lines: &[String]) -> Result<InstructionTable, String> {
+    let mut labels = Labels::new();
+    let mut statements: Vec<(usize, &str)> = Vec::new();
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let line_number = line_index + 1;
+        let mut statement = strip_comment(line).trim();
+
+        if let Some(colon) = statement.find(':') {
+            let label = statement[..colon].trim();
+
+            if !is_identifier(label) {
+                return Err(format!("line {0}: invalid label '{1}'", line_number, label));
+            }
+
+            if labels.insert(String::from(label), statements.len()).is_some() {
+                return Err(format!("line {0}: duplicate label '{1}'", line_number, label));
+            }
+
+            statement = statement[colon + 1..].trim();
+        }
+
+        if !statement.is_empty() {
+            statements.push((line_number, statement));
+        }
+    }
+
+    statements.into_iter()
+        .enumerate()
+        .map(|(index, (line_number, statement))| parse_instruction(statement, index, &labels)
+            .map(|instruction| (index, instruction))
+            .map_err(|error| format!("line {0}: {1}", line_number, error))
+        )
+        .collect()
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', ';']) {
+        Some(start) => &line[..start],
+        None => line,
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' =>
+            chars.all(|char| char.is_ascii_alphanumeric() || char == '_'),
+        _ => false,
+    }
+}
+
+/// Parses a single instruction located at `index`, resolving jump labels against `labels`.
+pub fn parse_instruction(s: &str, index: InstructionIndex, labels: &Labels) -> Result<Instruction, String> {
+    let tokens: Vec<&str> = s.split_whitespace().collect();
+
+    let (mnemonic, arguments) = match tokens.split_first() {
+        Some((mnemonic, arguments)) => (mnemonic.to_lowercase(), arguments),
+        None => return Err(String::from("Missing instruction")),
+    };
+
+    let instruction = match (mnemonic.as_str(), arguments) {
+        ("nop", [offset]) => Instruction::new(NOP, parse_offset(offset, index, labels)?),
+        ("acc", [value]) => Instruction::new(ACC, parse_delta(value)?),
+        ("jmp", [offset]) => Instruction::new(JMP, parse_offset(offset, index, labels)?),
+        ("add", [register, operand]) => Instruction::arithmetic(ADD, parse_register(register)?, parse_operand(operand)?),
+        ("mul", [register, operand]) => Instruction::arithmetic(MUL, parse_register(register)?, parse_operand(operand)?),
+        ("set", [register, operand]) => Instruction::arithmetic(SET, parse_register(register)?, parse_operand(operand)?),
+        ("jz", [register, offset]) => Instruction::conditional_jump(JZ, parse_register(register)?, parse_offset(offset, index, labels)?),
+        ("jnz", [register, offset]) => Instruction::conditional_jump(JNZ, parse_register(register)?, parse_offset(offset, index, labels)?),
+        ("halt", []) => Instruction::new(HALT, 0),
+        ("nop", _) | ("acc", _) | ("jmp", _) => return Err(format!("{0} expects one operand: {1}", mnemonic, s)),
+        ("add", _) | ("mul", _) | ("set", _) => return Err(format!("{0} expects a register and an operand: {1}", mnemonic, s)),
+        ("jz", _) | ("jnz", _) => return Err(format!("{0} expects a register and a target: {1}", mnemonic, s)),
+        ("halt", _) => return Err(format!("halt expects no operands: {0}", s)),
+        _ => return Err(format!("Unknown instruction: {0}", s)),
+    };
+
+    Ok(instruction)
+}
+
+fn parse_delta(s: &str) -> Result<InstructionDelta, String> {
+    s.parse::<InstructionDelta>()
+        .map_err(|_| format!("Invalid number: {0}", s))
+}
+
+fn parse_offset(s: &str, index: InstructionIndex, labels: &Labels) -> Result<InstructionDelta, String> {
+    if !is_identifier(s) {
+        return parse_delta(s);
+    }
+
+    match labels.get(s) {
+        Some(&target) => Ok(target as InstructionDelta - index as InstructionDelta),
+        None => Err(format!("Unknown label: {0}", s)),
+    }
+}
+
+fn parse_register(s: &str) -> Result<Register, String> {
+    if is_identifier(s) {
+        Ok(s.to_lowercase())
+    } else {
+        Err(format!("Invalid register: {0}", s))
+    }
+}
+
+fn parse_operand(s: &str) -> Result<Operand, String> {
+    if is_identifier(s) {
+        parse_register(s).map(Operand::Register)
+    } else {
+        s.parse()
+            .map(Operand::Value)
+            .map_err(|_| format!("Invalid operand: {0}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ACCUMULATOR, DEFAULT_STEP_LIMIT, Exit, read_register, run_program};
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter()
+            .map(|line| String::from(*line))
+            .collect()
+    }
+
+    #[test]
+    fn classic_instructions_are_unchanged() {
+        let instructions = assemble(&lines(&["nop +0", "acc -99", "jmp +4"])).unwrap();
+
+        assert_eq!(instructions[&0], Instruction::new(NOP, 0));
+        assert_eq!(instructions[&1], Instruction::new(ACC, -99));
+        assert_eq!(instructions[&2], Instruction::new(JMP, 4));
+    }
+
+    #[test]
+    fn labels_and_comments() {
+        let instructions = assemble(&lines(&[
+            "# computes 5 * 4 in acc",
+            "    set n 5",
+            "loop:",
+            "    jz n done   ; leave once n is zero",
+            "    add acc 4",
+            "    add n -1",
+            "    jmp loop",
+            "done: halt",
+        ])).unwrap();
+
+        assert_eq!(instructions.len(), 6);
+        assert_eq!(instructions[&1], Instruction::conditional_jump(JZ, String::from("n"), 4));
+        assert_eq!(instructions[&4], Instruction::new(JMP, -3));
+
+        let (exit, registers, _call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+        assert_eq!(exit, Exit::Terminated);
+        assert_eq!(read_register(&registers, ACCUMULATOR), 20);
+        assert_eq!(read_register(&registers, "n"), 0);
+    }
+
+    #[test]
+    fn display_prints_assembly() {
+        for line in &["nop -3", "acc +7", "jmp +0", "add a -2", "mul b c", "set acc 1", "jz a -1", "jnz x +2", "halt"] {
+            assert_eq!(line.parse::<Instruction>().unwrap().to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn round_trips_bundled_input() {
+        for line in include_str!("../resources/input").lines() {
+            let instruction = line.parse::<Instruction>()
+                .unwrap_or_else(|error| panic!("{0}: {1}", error, line));
+
+            assert_eq!(instruction.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        assert_eq!(assemble(&lines(&["nop +0", "jmp nowhere"])), Err(String::from("line 2: Unknown label: nowhere")));
+        assert_eq!(assemble(&lines(&["a:", "a: halt"])), Err(String::from("line 2: duplicate label 'a'")));
+        assert_eq!(assemble(&lines(&["", "add 3 a"])), Err(String::from("line 2: Invalid register: 3")));
+        assert_eq!(assemble(&lines(&["acc"])), Err(String::from("line 1: acc expects one operand: acc")));
+        assert_eq!(assemble(&lines(&["acc +x1"])), Err(String::from("line 1: Invalid number: +x1")));
+        assert_eq!(assemble(&lines(&["swap a b"])), Err(String::from("line 1: Unknown instruction: swap a b")));
+    }
+}
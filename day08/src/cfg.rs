@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+use crate::{has_conditional_jumps, Instruction, InstructionIndex, InstructionTable, OpCode};
+
+/// The control-flow graph of a program: conditional jumps have two successors, every other instruction one.
+///
+/// Node `len` stands for termination; HALT and every jump at or past the end of the program lead there.
+pub struct ControlFlowGraph {
+    /// Jumps before the first instruction are left out, as they can never terminate
+    successors: Vec<Vec<InstructionIndex>>,
+}
+
+impl ControlFlowGraph {
+    pub fn from(instructions: &InstructionTable) -> Self {
+        let successors = (0..instructions.len())
+            .map(|index| successors(&instructions[&index], index, instructions.len()))
+            .collect();
+
+        Self {
+            successors,
+        }
+    }
+
+    fn end(&self) -> InstructionIndex {
+        self.successors.len()
+    }
+
+    /// For every instruction whether execution starting there terminates, computed by a reverse
+    /// breadth-first search from the end node.
+    pub fn terminating(&self) -> Vec<bool> {
+        let mut predecessors: Vec<Vec<InstructionIndex>> = vec![Vec::new(); self.end() + 1];
+
+        for (index, successors) in self.successors.iter().enumerate() {
+            for &successor in successors {
+                predecessors[successor].push(index);
+            }
+        }
+
+        let mut terminating = vec![false; self.end() + 1];
+        terminating[self.end()] = true;
+
+        let mut pending: VecDeque<InstructionIndex> = VecDeque::new();
+        pending.push_back(self.end());
+
+        while let Some(index) = pending.pop_front() {
+            for &predecessor in &predecessors[index] {
+                if !terminating[predecessor] {
+                    terminating[predecessor] = true;
+                    pending.push_back(predecessor);
+                }
+            }
+        }
+
+        terminating
+    }
+
+    /// For every instruction whether some execution from the start can reach it, ignoring register values.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.end() + 1];
+        let mut pending: VecDeque<InstructionIndex> = VecDeque::new();
+
+        if self.end() > 0 {
+            reachable[0] = true;
+            pending.push_back(0);
+        } else {
+            reachable[self.end()] = true;
+        }
+
+        while let Some(index) = pending.pop_front() {
+            if index == self.end() {
+                continue;
+            }
+
+            for &successor in &self.successors[index] {
+                if !reachable[successor] {
+                    reachable[successor] = true;
+                    pending.push_back(successor);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    /// The instructions executed from the start until termination or the first repeated instruction.
+    ///
+    /// Only meaningful without conditional jumps; a conditional jump is assumed to fall through.
+    pub fn execution_path(&self) -> Vec<InstructionIndex> {
+        let mut visited = vec![false; self.end()];
+        let mut path = Vec::new();
+        let mut current = Some(0);
+
+        while let Some(index) = current {
+            if index >= self.end() || visited[index] {
+                break;
+            }
+
+            visited[index] = true;
+            path.push(index);
+            current = self.successors[index].last().copied();
+        }
+
+        path
+    }
+
+    /// Finds the NOP or JMP that, when flipped, makes the program terminate.
+    ///
+    /// Only instructions on the original execution path matter, and flipping one of them helps iff its new successor
+    /// terminates in the unmodified program: that path cannot lead back through the flipped instruction, as the
+    /// original program would terminate otherwise. Returns `None` if there is no such instruction, the program
+    /// already terminates or its control flow depends on registers (conditional jumps).
+    pub fn find_flip(&self, instructions: &InstructionTable) -> Option<InstructionIndex> {
+        if has_conditional_jumps(instructions) {
+            return None;
+        }
+
+        let terminating = self.terminating();
+
+        if terminating[0] {
+            return None;
+        }
+
+        self.execution_path().into_iter()
+            .find(|&index| instructions[&index].flipped()
+                .map(|flipped| successors(&flipped, index, self.end()).iter().any(|&successor| terminating[successor]))
+                .unwrap_or(false)
+            )
+    }
+}
+
+/// The indices that may be executed after `instruction`, clamped to `len` when past the end of the program.
+/// A conditional jump lists the fall-through successor first.
+pub fn successors(instruction: &Instruction, index: InstructionIndex, len: usize) -> Vec<InstructionIndex> {
+    let offsets = match instruction.opcode {
+        OpCode::JMP => vec![instruction.target as i64],
+        OpCode::JZ | OpCode::JNZ => vec![1, instruction.target as i64],
+        OpCode::HALT => return vec![len],
+        _ => vec![1],
+    };
+
+    offsets.into_iter()
+        .map(|offset| index as i64 + offset)
+        .filter(|&successor| successor >= 0)
+        .map(|successor| (successor as usize).min(len))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{example1, program};
+
+    #[test]
+    fn terminating_example1() {
+        let cfg = ControlFlowGraph::from(&example1());
+
+        assert_eq!(cfg.terminating(), vec![false, false, false, false, false, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn execution_path_stops_before_repeating() {
+        let cfg = ControlFlowGraph::from(&example1());
+
+        assert_eq!(cfg.execution_path(), vec![0, 1, 2, 6, 7, 3, 4]);
+    }
+
+    #[test]
+    fn find_flip_example1() {
+        let instructions = example1();
+
+        assert_eq!(ControlFlowGraph::from(&instructions).find_flip(&instructions), Some(7));
+    }
+
+    #[test]
+    fn find_flip_uses_nop_operand() {
+        // Only turning the NOP into "jmp +4" escapes the loop; flipping either JMP leads back into it
+        let instructions = program(&["nop +4", "acc +1", "jmp -2", "jmp -3", "acc +5"]);
+
+        assert_eq!(ControlFlowGraph::from(&instructions).find_flip(&instructions), Some(0));
+    }
+
+    #[test]
+    fn find_flip_ignores_terminating_program() {
+        let instructions = program(&["acc +1", "jmp +1"]);
+
+        assert_eq!(ControlFlowGraph::from(&instructions).find_flip(&instructions), None);
+    }
+
+    #[test]
+    fn jumps_before_start_never_terminate() {
+        let instructions = program(&["jmp -1"]);
+
+        assert_eq!(ControlFlowGraph::from(&instructions).terminating(), vec![false, true]);
+        assert_eq!(ControlFlowGraph::from(&instructions).find_flip(&instructions), Some(0));
+    }
+
+    #[test]
+    fn conditional_jumps_and_halt_have_their_own_successors() {
+        let instructions = program(&["jz a +2", "halt", "jmp -2"]);
+
+        assert_eq!(ControlFlowGraph::from(&instructions).successors, vec![vec![1, 2], vec![3], vec![0]]);
+        assert_eq!(ControlFlowGraph::from(&instructions).terminating(), vec![true, true, true, true]);
+    }
+
+    #[test]
+    fn reachable_example1() {
+        let cfg = ControlFlowGraph::from(&example1());
+
+        assert_eq!(cfg.reachable(), vec![true, true, true, true, true, false, true, true, false, false]);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::{Accumulator, ACCUMULATOR, CallStack, Coverage, execute, has_conditional_jumps, InstructionIndex, InstructionTable, read_register, Register, Registers, StateHistory};
+
+#[derive(Debug, PartialEq)]
+enum Watchpoint {
+    /// Stop whenever the accumulator changes
+    Change,
+    /// Stop when the accumulator becomes the given value
+    Value(Accumulator),
+}
+
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Terminated,
+    Halted,
+    Breakpoint(InstructionIndex),
+    Watchpoint(Accumulator, Accumulator),
+    /// The instruction at the index was about to be executed a second time (with the same registers if the program has
+    /// conditional jumps)
+    Loop(InstructionIndex),
+    /// The given number of instructions were executed without stopping for any other reason
+    StepLimit(usize),
+    /// The next instruction could not be executed
+    Fault(String),
+}
+
+/// Steps through a program, forwards and backwards.
+///
+/// Reverse stepping replays the call stack: popping the last executed instruction restores the instruction counter,
+/// and the undo log restores the register it wrote (if any). Watchpoints apply to the accumulator.
+pub struct Debugger<'a> {
+    instructions: &'a InstructionTable,
+    instruction_counter: InstructionIndex,
+    registers: Registers,
+    halted: bool,
+    /// Why the instruction at the instruction counter could not be executed; it stays on the call stack
+    fault: Option<String>,
+    /// The most instructions a single `continue` executes
+    step_limit: usize,
+    call_stack: CallStack,
+    /// The previous value of the register written by each executed instruction, parallel to the call stack
+    undo_log: Vec<Option<(Register, Option<Accumulator>)>>,
+    coverage: Coverage,
+    breakpoints: BTreeSet<InstructionIndex>,
+    watchpoint: Option<Watchpoint>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(instructions: &'a InstructionTable, step_limit: usize) -> Self {
+        Self {
+            instructions,
+            instruction_counter: 0,
+            registers: Registers::new(),
+            halted: false,
+            fault: None,
+            step_limit,
+            call_stack: CallStack::new(),
+            undo_log: Vec::new(),
+            coverage: Coverage::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoint: None,
+        }
+    }
+
+    fn accumulator(&self) -> Accumulator {
+        read_register(&self.registers, ACCUMULATOR)
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.halted || self.fault.is_some() || self.instruction_counter >= self.instructions.len()
+    }
+
+    /// Executes the next instruction; returns false if the program already terminated.
+    fn step(&mut self) -> bool {
+        if self.is_terminated() {
+            return false;
+        }
+
+        let instruction = &self.instructions[&self.instruction_counter];
+
+        self.call_stack.push(self.instruction_counter);
+        self.undo_log.push(instruction.written_register()
+            .map(|register| (String::from(register), self.registers.get(register).copied()))
+        );
+        *self.coverage.entry(self.instruction_counter).or_insert(0) += 1;
+
+        match execute(instruction, self.instruction_counter, &mut self.registers) {
+            Ok(Some(instruction_counter)) => self.instruction_counter = instruction_counter,
+            Ok(None) => self.halted = true,
+            Err(error) => self.fault = Some(error),
+        }
+
+        true
+    }
+
+    /// Undoes the last executed instruction; returns false if nothing has been executed yet.
+    fn reverse_step(&mut self) -> bool {
+        let previous = match self.call_stack.pop() {
+            Some(previous) => previous,
+            None => return false,
+        };
+
+        if let Some(Some((register, value))) = self.undo_log.pop() {
+            match value {
+                Some(value) => self.registers.insert(register, value),
+                None => self.registers.remove(&register),
+            };
+        }
+
+        if let Some(count) = self.coverage.get_mut(&previous) {
+            *count -= 1;
+        }
+
+        self.instruction_counter = previous;
+        self.halted = false;
+        self.fault = None;
+
+        true
+    }
+
+    fn watchpoint_triggered(&self, before: Accumulator) -> bool {
+        match self.watchpoint {
+            Some(Watchpoint::Change) => before != self.accumulator(),
+            Some(Watchpoint::Value(value)) => before != value && self.accumulator() == value,
+            None => false,
+        }
+    }
+
+    /// Runs until the program terminates or fails, hits a breakpoint or watchpoint, is about to loop, or has executed
+    /// `step_limit` instructions. Always executes at least one instruction, so continuing from a breakpoint moves past
+    /// it.
+    fn resume(&mut self) -> Stop {
+        let compare_states = has_conditional_jumps(self.instructions);
+        let mut states = StateHistory::new();
+        let mut first = true;
+        let mut steps = 0;
+
+        loop {
+            if self.halted {
+                return Stop::Halted;
+            }
+
+            if let Some(error) = &self.fault {
+                return Stop::Fault(error.clone());
+            }
+
+            if self.is_terminated() {
+                return Stop::Terminated;
+            }
+
+            let repeated = compare_states
+                && states.insert(self.instructions, &self.call_stack, self.instruction_counter, &self.registers).is_some();
+
+            if !first {
+                if self.breakpoints.contains(&self.instruction_counter) {
+                    return Stop::Breakpoint(self.instruction_counter);
+                }
+
+                let looping = if compare_states {
+                    repeated
+                } else {
+                    self.coverage.get(&self.instruction_counter).copied().unwrap_or(0) > 0
+                };
+
+                if looping {
+                    return Stop::Loop(self.instruction_counter);
+                }
+
+                if steps >= self.step_limit {
+                    return Stop::StepLimit(steps);
+                }
+            }
+
+            first = false;
+
+            let before = self.accumulator();
+            self.step();
+            steps += 1;
+
+            if self.watchpoint_triggered(before) {
+                return Stop::Watchpoint(before, self.accumulator());
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        // The accumulator is always shown, other registers once they have been written
+        let mut state = format!("ip={0} acc={1}", self.instruction_counter, self.accumulator());
+
+        for (register, value) in self.registers.iter().filter(|(register, _)| *register != ACCUMULATOR) {
+            state.push_str(&format!(" {0}={1}", register, value));
+        }
+
+        match self.instructions.get(&self.instruction_counter) {
+            _ if self.halted => format!("{0} steps={1} halted", state, self.call_stack.len()),
+            _ if self.fault.is_some() => format!("{0} steps={1} error: {2}", state, self.call_stack.len(), self.fault.as_ref().unwrap()),
+            Some(instruction) => format!("{0} steps={1} next: {2}", state, self.call_stack.len(), instruction),
+            None => format!("{0} steps={1} terminated", state, self.call_stack.len()),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or the end of input.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        writeln!(output, "{0}", self.describe())?;
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            match tokens.as_slice() {
+                [] => (),
+                ["quit"] | ["q"] => break,
+                ["help"] | ["h"] => writeln!(output, "commands: step [n], reverse-step [n], continue, break <ip>, delete <ip>, watch [<value>], unwatch, print, quit")?,
+                ["print"] | ["p"] => writeln!(output, "{0}", self.describe())?,
+                [command, count @ ..] if ["step", "s", "reverse-step", "rs"].contains(command) => {
+                    let count = match count {
+                        [] => Some(1),
+                        [count] => count.parse::<usize>().ok(),
+                        _ => None,
+                    };
+
+                    match count {
+                        Some(count) => {
+                            let reverse = *command == "reverse-step" || *command == "rs";
+
+                            for _ in 0..count {
+                                let moved = if reverse { self.reverse_step() } else { self.step() };
+
+                                if !moved {
+                                    writeln!(output, "{0}", if reverse { "at start of program" } else { "program terminated" })?;
+                                    break;
+                                }
+                            }
+
+                            writeln!(output, "{0}", self.describe())?;
+                        }
+                        None => writeln!(output, "usage: {0} [n]", command)?,
+                    }
+                }
+                ["continue"] | ["c"] => {
+                    match self.resume() {
+                        Stop::Terminated => writeln!(output, "program terminated")?,
+                        Stop::Halted => writeln!(output, "program halted")?,
+                        Stop::Breakpoint(index) => writeln!(output, "breakpoint at {0}", index)?,
+                        Stop::Watchpoint(before, after) => writeln!(output, "watchpoint: acc {0} -> {1}", before, after)?,
+                        Stop::Loop(index) => writeln!(output, "infinite loop: {0} would be executed again", index)?,
+                        Stop::StepLimit(steps) => writeln!(output, "step limit: {0} instructions executed", steps)?,
+                        Stop::Fault(error) => writeln!(output, "error: {0}", error)?,
+                    }
+
+                    writeln!(output, "{0}", self.describe())?;
+                }
+                ["break", index] | ["b", index] => match index.parse::<InstructionIndex>() {
+                    Ok(index) => {
+                        self.breakpoints.insert(index);
+                        writeln!(output, "breakpoint at {0}", index)?;
+                    }
+                    Err(_) => writeln!(output, "invalid instruction index: {0}", index)?,
+                },
+                ["delete", index] | ["d", index] => match index.parse::<InstructionIndex>() {
+                    Ok(index) => {
+                        self.breakpoints.remove(&index);
+                        writeln!(output, "deleted breakpoint at {0}", index)?;
+                    }
+                    Err(_) => writeln!(output, "invalid instruction index: {0}", index)?,
+                },
+                ["watch"] | ["w"] => {
+                    self.watchpoint = Some(Watchpoint::Change);
+                    writeln!(output, "watching acc for changes")?;
+                }
+                ["watch", value] | ["w", value] => match value.parse::<Accumulator>() {
+                    Ok(value) => {
+                        self.watchpoint = Some(Watchpoint::Value(value));
+                        writeln!(output, "watching acc for {0}", value)?;
+                    }
+                    Err(_) => writeln!(output, "invalid accumulator value: {0}", value)?,
+                },
+                ["unwatch"] => {
+                    self.watchpoint = None;
+                    writeln!(output, "watchpoint removed")?;
+                }
+                _ => writeln!(output, "unknown command: {0} (try help)", line.trim())?,
+            }
+
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_STEP_LIMIT;
+    use crate::tests::{example1, program};
+
+    fn session(commands: &str) -> String {
+        let instructions = example1();
+        let mut output: Vec<u8> = Vec::new();
+
+        Debugger::new(&instructions, DEFAULT_STEP_LIMIT).run(commands.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn step_and_reverse_step_restore_state() {
+        let instructions = example1();
+        let mut debugger = Debugger::new(&instructions, DEFAULT_STEP_LIMIT);
+
+        for _ in 0..4 {
+            assert!(debugger.step());
+        }
+
+        assert_eq!((debugger.instruction_counter, debugger.accumulator()), (7, 2));
+
+        assert!(debugger.reverse_step());
+        assert!(debugger.reverse_step());
+        assert_eq!((debugger.instruction_counter, debugger.accumulator()), (2, 1));
+
+        assert!(debugger.reverse_step());
+        assert!(debugger.reverse_step());
+        assert!(!debugger.reverse_step());
+        assert_eq!((debugger.instruction_counter, debugger.accumulator()), (0, 0));
+    }
+
+    #[test]
+    fn continue_detects_infinite_loop() {
+        let instructions = example1();
+        let mut debugger = Debugger::new(&instructions, DEFAULT_STEP_LIMIT);
+
+        assert_eq!(debugger.resume(), Stop::Loop(1));
+        assert_eq!(debugger.accumulator(), 5);
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoints_and_watchpoints() {
+        let instructions = example1();
+        let mut debugger = Debugger::new(&instructions, DEFAULT_STEP_LIMIT);
+
+        debugger.breakpoints.insert(3);
+        assert_eq!(debugger.resume(), Stop::Breakpoint(3));
+
+        debugger.watchpoint = Some(Watchpoint::Value(5));
+        assert_eq!(debugger.resume(), Stop::Watchpoint(2, 5));
+        assert_eq!(debugger.instruction_counter, 4);
+    }
+
+    #[test]
+    fn run_executes_commands() {
+        let output = session("step 2\nprint\nrs\nbogus\nquit\nstep\n");
+
+        assert_eq!(output, "ip=0 acc=0 steps=0 next: nop +0\n\
+            > ip=2 acc=1 steps=2 next: jmp +4\n\
+            > ip=2 acc=1 steps=2 next: jmp +4\n\
+            > ip=1 acc=0 steps=1 next: acc +1\n\
+            > unknown command: bogus (try help)\n\
+            > ");
+    }
+
+    #[test]
+    fn reverse_step_restores_registers() {
+        let instructions = program(&["set a 3", "mul a a", "add acc a", "halt"]);
+        let mut debugger = Debugger::new(&instructions, DEFAULT_STEP_LIMIT);
+
+        assert_eq!(debugger.resume(), Stop::Halted);
+        assert_eq!(debugger.describe(), "ip=3 acc=9 a=9 steps=4 halted");
+
+        assert!(debugger.reverse_step());
+        assert!(debugger.reverse_step());
+        assert_eq!(debugger.describe(), "ip=2 acc=0 a=9 steps=2 next: add acc a");
+
+        assert!(debugger.reverse_step());
+        assert!(debugger.reverse_step());
+        assert_eq!(debugger.describe(), "ip=0 acc=0 steps=0 next: set a 3");
+    }
+
+    #[test]
+    fn resume_stops_at_step_limit() {
+        // Counts up forever without repeating a state
+        let instructions = program(&["set a 1", "add a 1", "jnz a -1"]);
+        let mut debugger = Debugger::new(&instructions, 100);
+
+        assert_eq!(debugger.resume(), Stop::StepLimit(100));
+        assert_eq!(debugger.resume(), Stop::StepLimit(100));
+        assert_eq!(debugger.call_stack.len(), 200);
+    }
+
+    #[test]
+    fn faults_stop_the_program_until_reversed() {
+        let instructions = program(&["acc +1", "jmp -2"]);
+        let mut debugger = Debugger::new(&instructions, DEFAULT_STEP_LIMIT);
+
+        assert_eq!(debugger.resume(), Stop::Fault(String::from("Jump from 1 by -2 leaves the program")));
+        assert_eq!(debugger.describe(), "ip=1 acc=1 steps=2 error: Jump from 1 by -2 leaves the program");
+        assert!(!debugger.step());
+
+        assert!(debugger.reverse_step());
+        assert_eq!(debugger.describe(), "ip=1 acc=1 steps=1 next: jmp -2");
+    }
+}
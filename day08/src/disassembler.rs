@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use crate::cfg::ControlFlowGraph;
+use crate::{InstructionDelta, InstructionIndex, InstructionTable, OpCode};
+
+/// The absolute target of a jump, which may lie before the start or past the end of the program.
+fn jump_target(opcode: &OpCode, index: InstructionIndex, target: InstructionDelta) -> Option<i64> {
+    match opcode {
+        OpCode::JMP | OpCode::JZ | OpCode::JNZ => Some(index as i64 + target as i64),
+        _ => None,
+    }
+}
+
+/// One line per instruction: its index and assembly, followed by comments naming the jump target (`-> 7`), the jumps
+/// leading here (`<- 2, 4`) and whether the instruction is unreachable from the start.
+pub fn disassemble(instructions: &InstructionTable) -> Vec<String> {
+    let reachable = ControlFlowGraph::from(instructions).reachable();
+
+    let mut incoming: BTreeMap<InstructionIndex, Vec<InstructionIndex>> = BTreeMap::new();
+
+    for index in 0..instructions.len() {
+        let instruction = &instructions[&index];
+
+        if let Some(target) = jump_target(&instruction.opcode, index, instruction.target) {
+            if target >= 0 {
+                incoming.entry(target as InstructionIndex).or_default().push(index);
+            }
+        }
+    }
+
+    (0..instructions.len())
+        .map(|index| {
+            let instruction = &instructions[&index];
+            let mut annotations: Vec<String> = Vec::new();
+
+            match jump_target(&instruction.opcode, index, instruction.target) {
+                Some(target) if target < 0 => annotations.push(String::from("-> before start")),
+                Some(target) if target as usize >= instructions.len() => annotations.push(String::from("-> end")),
+                Some(target) => annotations.push(format!("-> {0}", target)),
+                None => (),
+            }
+
+            if let Some(sources) = incoming.get(&index) {
+                let sources: Vec<String> = sources.iter().map(InstructionIndex::to_string).collect();
+                annotations.push(format!("<- {0}", sources.join(", ")));
+            }
+
+            if !reachable[index] {
+                annotations.push(String::from("unreachable"));
+            }
+
+            if annotations.is_empty() {
+                format!("{0:>4}  {1}", index, instruction)
+            } else {
+                format!("{0:>4}  {1:<16} ; {2}", index, instruction.to_string(), annotations.join("; "))
+            }
+        })
+        .collect()
+}
+
+/// Removes the instructions unreachable from the start and rewrites the relative jumps around the gaps.
+///
+/// Jumps past the end keep leaving the program and jumps before the start keep their absolute target. NOP operands
+/// are rewritten the same way; a NOP pointing at removed code points at the first kept instruction after it instead.
+pub fn normalize(instructions: &InstructionTable) -> InstructionTable {
+    let reachable = ControlFlowGraph::from(instructions).reachable();
+    let len = instructions.len();
+
+    // Where every index ends up: kept instructions at their new index, removed ones at the index of the next kept
+    // instruction, and the end of the program at the end of the normalized one
+    let mut new_indices: Vec<InstructionIndex> = Vec::with_capacity(len + 1);
+    let mut kept = 0;
+
+    for &is_reachable in &reachable[..len] {
+        new_indices.push(kept);
+
+        if is_reachable {
+            kept += 1;
+        }
+    }
+
+    new_indices.push(kept);
+
+    (0..len)
+        .filter(|&index| reachable[index])
+        .map(|index| {
+            let new_index = new_indices[index];
+            let mut instruction = instructions[&index].clone();
+
+            let opcode = if instruction.opcode == OpCode::NOP { OpCode::JMP } else { instruction.opcode.clone() };
+
+            if let Some(target) = jump_target(&opcode, index, instruction.target) {
+                let new_target = if target < 0 {
+                    target
+                } else {
+                    new_indices[(target as usize).min(len)] as i64
+                };
+
+                instruction.target = (new_target - new_index as i64) as InstructionDelta;
+            }
+
+            (new_index, instruction)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_instructions;
+    use crate::tests::{example1, program};
+
+    fn assembly(instructions: &InstructionTable) -> Vec<String> {
+        (0..instructions.len())
+            .map(|index| instructions[&index].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn disassemble_example1() {
+        assert_eq!(disassemble(&example1()), vec![
+            "   0  nop +0",
+            "   1  acc +1           ; <- 4",
+            "   2  jmp +4           ; -> 6",
+            "   3  acc +3           ; <- 7",
+            "   4  jmp -3           ; -> 1",
+            "   5  acc -99          ; unreachable",
+            "   6  acc +1           ; <- 2",
+            "   7  jmp -4           ; -> 3",
+            "   8  acc +6           ; unreachable",
+        ]);
+    }
+
+    #[test]
+    fn disassemble_jumps_out_of_program() {
+        assert_eq!(disassemble(&program(&["jz a +5", "jmp -2"])), vec![
+            "   0  jz a +5          ; -> end",
+            "   1  jmp -2           ; -> before start",
+        ]);
+    }
+
+    #[test]
+    fn normalize_example1() {
+        let instructions = example1();
+        let normalized = normalize(&instructions);
+
+        assert_eq!(assembly(&normalized), vec!["nop +0", "acc +1", "jmp +3", "acc +3", "jmp -3", "acc +1", "jmp -3"]);
+        assert_eq!(run_instructions(&normalized).1, run_instructions(&instructions).1);
+    }
+
+    #[test]
+    fn normalize_keeps_exits_and_nop_operands() {
+        // The NOP points at the kept "jz a +9", which leaves the program
+        let instructions = program(&["nop +3", "jmp +2", "acc +1", "jz a +9", "halt"]);
+
+        assert_eq!(assembly(&normalize(&instructions)), vec!["nop +2", "jmp +1", "jz a +2", "halt"]);
+    }
+
+    #[test]
+    fn normalize_moves_nop_operands_off_removed_code() {
+        // The NOP points at the removed "acc +2", so it now points at the "halt" that takes its place
+        let instructions = program(&["nop +3", "jmp +3", "acc +1", "acc +2", "halt", "nop -4"]);
+
+        assert_eq!(assembly(&normalize(&instructions)), vec!["nop +2", "jmp +1", "halt"]);
+    }
+}
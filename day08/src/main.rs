@@ -1,24 +1,92 @@
-use std::collections::{HashMap, HashSet};
-use std::io::{BufRead, stdin};
-use std::ops::Add;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, stdin, stdout};
+use std::process;
 use std::str::FromStr;
 
-use crate::OpCode::{ACC, JMP, NOP};
+use crate::assembler::{assemble, parse_instruction};
+use crate::cfg::ControlFlowGraph;
+use crate::debugger::Debugger;
+use crate::disassembler::{disassemble, normalize};
+use crate::trace::{Format, Profile, trace, write_trace};
+use crate::OpCode::{ACC, ADD, HALT, JMP, JNZ, JZ, MUL, NOP, SET};
+
+mod assembler;
+mod cfg;
+mod debugger;
+mod disassembler;
+mod trace;
 
 type InstructionIndex = usize;
 type InstructionDelta = i32;
+type Register = String;
+type Registers = BTreeMap<Register, Accumulator>;
+
+/// The register ACC operates on, also used to report the result of a run.
+const ACCUMULATOR: &str = "acc";
+
+/// How many instructions a run may execute before it is given up as non-terminating; programs with conditional jumps
+/// can run forever without ever repeating a state.
+const DEFAULT_STEP_LIMIT: usize = 1_000_000;
 
-#[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq)]
 enum OpCode {
     NOP,
     ACC,
     JMP,
+    ADD,
+    MUL,
+    SET,
+    JZ,
+    JNZ,
+    HALT,
+}
+
+impl OpCode {
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            NOP => "nop",
+            ACC => "acc",
+            JMP => "jmp",
+            ADD => "add",
+            MUL => "mul",
+            SET => "set",
+            JZ => "jz",
+            JNZ => "jnz",
+            HALT => "halt",
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Value(Accumulator),
+    Register(Register),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{0}", value),
+            Operand::Register(register) => write!(f, "{0}", register),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Instruction {
     opcode: OpCode,
+    /// Jump offset of JMP, JZ and JNZ, increment of ACC; NOP keeps it but ignores it
     target: InstructionDelta,
+    /// Register written by ADD, MUL and SET or tested by JZ and JNZ
+    register: Register,
+    /// Source operand of ADD, MUL and SET
+    operand: Operand,
 }
 
 impl Instruction {
@@ -26,6 +94,51 @@ impl Instruction {
         Instruction {
             opcode,
             target,
+            register: String::from(ACCUMULATOR),
+            operand: Operand::Value(0),
+        }
+    }
+
+    fn arithmetic(opcode: OpCode, register: Register, operand: Operand) -> Instruction {
+        Instruction {
+            register,
+            operand,
+            ..Instruction::new(opcode, 0)
+        }
+    }
+
+    fn conditional_jump(opcode: OpCode, register: Register, target: InstructionDelta) -> Instruction {
+        Instruction {
+            register,
+            ..Instruction::new(opcode, target)
+        }
+    }
+
+    /// The instruction with NOP and JMP swapped, keeping the target; no other instruction can be flipped.
+    fn flipped(&self) -> Option<Instruction> {
+        match self.opcode {
+            NOP => Some(Instruction::new(JMP, self.target)),
+            JMP => Some(Instruction::new(NOP, self.target)),
+            _ => None,
+        }
+    }
+
+    /// The operands as written in assembly, e.g. `+4` or `a b`; empty for HALT.
+    fn operands(&self) -> String {
+        match self.opcode {
+            NOP | ACC | JMP => format!("{0:+}", self.target),
+            ADD | MUL | SET => format!("{0} {1}", self.register, self.operand),
+            JZ | JNZ => format!("{0} {1:+}", self.register, self.target),
+            HALT => String::new(),
+        }
+    }
+
+    /// The register this instruction writes to, if any.
+    fn written_register(&self) -> Option<&str> {
+        match self.opcode {
+            ACC => Some(ACCUMULATOR),
+            ADD | MUL | SET => Some(&self.register),
+            _ => None,
         }
     }
 }
@@ -33,180 +146,496 @@ impl Instruction {
 impl FromStr for Instruction {
     type Err = String;
 
+    /// Parses a single instruction without labels; see `assembler::assemble` for whole programs.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut tokens = s.split_whitespace();
-
-        let instruction = tokens.next();
+        parse_instruction(s, 0, &HashMap::new())
+    }
+}
 
-        if instruction.is_none() {
-            return Err(String::from("Could not extract instruction from: ").add(s));
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opcode {
+            HALT => write!(f, "{0}", self.opcode.mnemonic()),
+            _ => write!(f, "{0} {1}", self.opcode.mnemonic(), self.operands()),
         }
+    }
+}
 
-        let target = tokens.next()
-            .map(|target_string| target_string.parse::<InstructionDelta>()
-                .expect(String::from("Could not parse target into number: ").add(s).as_str())
-            );
-
-        if instruction.is_none() {
-            return Err(String::from("Could not extract target from: ").add(s));
-        }
+/// Why a run stopped.
+#[derive(Debug, PartialEq)]
+enum Exit {
+    /// Ran past the last instruction or executed HALT
+    Terminated,
+    /// Was about to repeat a state, so would never terminate
+    Loop,
+    /// Used up the given number of steps without terminating or repeating a state
+    StepLimit(usize),
+    /// An instruction could not be executed
+    Fault(String),
+}
 
-        let instruction = instruction.unwrap();
-        let target = target.unwrap();
+impl Exit {
+    fn is_successful(&self) -> bool {
+        *self == Exit::Terminated
+    }
+}
 
-        match instruction.to_lowercase().as_str() {
-            "nop" => Ok(Instruction::new(NOP, 0)),
-            "acc" => Ok(Instruction::new(ACC, target)),
-            "jmp" => Ok(Instruction::new(JMP, target)),
-            _ => Err(String::from("Unknown instruction: ").add(s))
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::Terminated => write!(f, "terminated"),
+            Exit::Loop => write!(f, "infinite loop"),
+            Exit::StepLimit(step_limit) => write!(f, "step limit of {0} reached", step_limit),
+            Exit::Fault(error) => write!(f, "error: {0}", error),
         }
     }
 }
 
 type InstructionTable = HashMap<InstructionIndex, Instruction>;
 type CallStack = Vec<InstructionIndex>;
-type Coverage = HashMap<InstructionIndex, u16>;
+type Coverage = HashMap<InstructionIndex, usize>;
 type Accumulator = i64;
 
 fn main() {
-    let input: Vec<String> = stdin().lock().lines()
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [] => {
+            let instructions = parse_instructions(&read_input());
+
+            // println!("instructions = {0:?}", instructions);
+
+            part1(&instructions);
+            part2(&instructions);
+        }
+        [command, path, step_limit @ ..] if command == "debug" => {
+            let instructions = parse_instructions(&read_program(path));
+
+            Debugger::new(&instructions, parse_step_limit(step_limit)).run(stdin().lock(), stdout())
+                .expect("Error while running debugger");
+        }
+        [command, format] if command == "trace" => {
+            let format = Format::from_str(format).unwrap_or_else(|error| {
+                eprintln!("{0}", error);
+                process::exit(1);
+            });
+
+            let instructions = parse_instructions(&read_input());
+            let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+            write_trace(&trace(&instructions, &call_stack, exit.is_successful()), &format, stdout().lock())
+                .expect("Error while writing trace");
+        }
+        [command] if command == "disassemble" => {
+            for line in disassemble(&parse_instructions(&read_input())) {
+                println!("{0}", line);
+            }
+        }
+        [command] if command == "normalize" => {
+            let instructions = normalize(&parse_instructions(&read_input()));
+
+            for index in 0..instructions.len() {
+                println!("{0}", instructions[&index]);
+            }
+        }
+        [command] if command == "profile" => {
+            let instructions = parse_instructions(&read_input());
+            let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+            let profile = Profile::from(&call_stack, exit.is_successful());
+
+            for line in profile.summary() {
+                println!("{0}", line);
+            }
+
+            let mut hits: Vec<(&InstructionIndex, &usize)> = profile.hits.iter().collect();
+            hits.sort_by(|(ip_a, count_a), (ip_b, count_b)| count_b.cmp(count_a).then(ip_a.cmp(ip_b)));
+
+            for (ip, count) in hits {
+                println!("{0:>5} {1:>8}  {2}", ip, count, instructions[ip]);
+            }
+        }
+        [command, path, step_limit @ ..] if command == "run" => {
+            let instructions = parse_instructions(&read_program(path));
+
+            let (exit, registers, call_stack, _coverage) = run_program(&instructions, parse_step_limit(step_limit));
+
+            println!("run: successful={0} steps={1} registers={2:?}", exit.is_successful(), call_stack.len(), registers);
+
+            if !exit.is_successful() {
+                println!("run: {0}", exit);
+            }
+        }
+        _ => {
+            eprintln!("usage: day08 [debug <program file> [max steps]|run <program file> [max steps]|trace jsonl|csv|profile|disassemble|normalize] < input");
+            process::exit(1);
+        }
+    }
+}
+
+fn read_input() -> Vec<String> {
+    stdin().lock().lines()
         .map(|maybe_line| maybe_line.expect("Error while reading line"))
-        .collect();
+        .collect()
+}
 
-    let mut line_number = 0;
+/// The optional step limit argument of `run` and `debug`.
+fn parse_step_limit(args: &[String]) -> usize {
+    match args {
+        [] => DEFAULT_STEP_LIMIT,
+        [step_limit] => step_limit.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Invalid step limit: {0}", step_limit);
+            process::exit(1);
+        }),
+        _ => {
+            eprintln!("Too many arguments: {0:?}", args);
+            process::exit(1);
+        }
+    }
+}
 
-    let instructions: InstructionTable = input.into_iter()
-        .map(|line| Instruction::from_str(&line).unwrap())
-        .map(|operation| {
-            let current_line = line_number;
-            line_number += 1;
+fn read_program(path: &str) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap_or_else(|error| {
+            eprintln!("Could not read {0}: {1}", path, error);
+            process::exit(1);
+        })
+        .lines()
+        .map(String::from)
+        .collect()
+}
 
-            (current_line, operation)
+fn parse_instructions(input: &[String]) -> InstructionTable {
+    assemble(input)
+        .unwrap_or_else(|error| {
+            eprintln!("{0}", error);
+            process::exit(1);
         })
-        .collect();
+}
 
-    // println!("instructions = {0:?}", instructions);
+fn update_instruction_counter(current_instruction_counter: InstructionIndex, delta: InstructionDelta) -> Result<InstructionIndex, String> {
+    let instruction_counter = if delta.is_negative() {
+        current_instruction_counter.checked_sub(delta.wrapping_abs() as u32 as usize)
+    } else {
+        current_instruction_counter.checked_add(delta as usize)
+    };
 
-    part1(&instructions);
-    part2(&instructions);
+    instruction_counter.ok_or_else(|| format!("Jump from {0} by {1:+} leaves the program", current_instruction_counter, delta))
 }
 
-fn update_instruction_counter(current_instruction_counter: InstructionIndex, delta: InstructionDelta) -> InstructionIndex {
-    if delta.is_negative() {
-        current_instruction_counter - delta.wrapping_abs() as u32 as usize
-    } else {
-        current_instruction_counter + delta as usize
+fn update_accumulator(accumulator: Accumulator, delta: InstructionDelta) -> Result<Accumulator, String> {
+    accumulator.checked_add(delta as i64)
+        .ok_or_else(|| format!("Overflow in {0}: {1} {2:+}", ACCUMULATOR, accumulator, delta))
+}
+
+fn read_operand(registers: &Registers, operand: &Operand) -> Accumulator {
+    match operand {
+        Operand::Value(value) => *value,
+        Operand::Register(register) => read_register(registers, register),
     }
 }
 
-fn update_accumulator(accumulator: Accumulator, delta: InstructionDelta) -> Accumulator {
-    accumulator + delta as i64
+/// Registers that were never written read as 0.
+fn read_register(registers: &Registers, register: &str) -> Accumulator {
+    registers.get(register).copied().unwrap_or(0)
+}
+
+/// Executes a single instruction, updating the registers, and returns the next instruction counter
+/// (`None` after HALT).
+///
+/// Fails without changing any register if an arithmetic instruction overflows or a jump leaves the program before
+/// its first instruction.
+fn execute(instruction: &Instruction, instruction_counter: InstructionIndex, registers: &mut Registers) -> Result<Option<InstructionIndex>, String> {
+    let next = instruction_counter + 1;
+
+    match instruction.opcode {
+        NOP => Ok(Some(next)),
+        ACC => {
+            let accumulator = update_accumulator(read_register(registers, ACCUMULATOR), instruction.target)?;
+            registers.insert(String::from(ACCUMULATOR), accumulator);
+            Ok(Some(next))
+        }
+        JMP => update_instruction_counter(instruction_counter, instruction.target).map(Some),
+        ADD | MUL | SET => {
+            let value = read_operand(registers, &instruction.operand);
+            let current = read_register(registers, &instruction.register);
+
+            let result = match instruction.opcode {
+                ADD => current.checked_add(value),
+                MUL => current.checked_mul(value),
+                _ => Some(value),
+            };
+
+            match result {
+                Some(result) => registers.insert(instruction.register.clone(), result),
+                None => return Err(format!("Overflow in {0}: {1} {2} {3}", instruction.register, current, instruction.opcode.mnemonic(), value)),
+            };
+
+            Ok(Some(next))
+        }
+        JZ | JNZ => {
+            let is_zero = read_register(registers, &instruction.register) == 0;
+
+            if is_zero == (instruction.opcode == JZ) {
+                update_instruction_counter(instruction_counter, instruction.target).map(Some)
+            } else {
+                Ok(Some(next))
+            }
+        }
+        HALT => Ok(None),
+    }
+}
+
+/// Whether the control flow of the program can depend on register values.
+///
+/// Without conditional jumps executing an instruction a second time means the program loops forever; with them only
+/// repeating the whole machine state (instruction counter and registers) does.
+fn has_conditional_jumps(instructions: &InstructionTable) -> bool {
+    instructions.values()
+        .any(|instruction| instruction.opcode == JZ || instruction.opcode == JNZ)
+}
+
+/// The machine states (instruction counter and registers) seen during a run.
+///
+/// Only a 64-bit hash and the step of each state are kept, so long runs do not hold a copy of the registers per step.
+/// If hashes collide, the earlier state is rebuilt by replaying the executed instructions and compared in full.
+struct StateHistory {
+    first_seen: HashMap<u64, usize>,
+    // Further steps whose (different) state has the same hash as the one in `first_seen`
+    collisions: HashMap<u64, Vec<usize>>,
+}
+
+impl StateHistory {
+    fn new() -> Self {
+        Self {
+            first_seen: HashMap::new(),
+            collisions: HashMap::new(),
+        }
+    }
+
+    /// Records the state after executing `executed` (every instruction since the program started) and returns the
+    /// earlier step with the same state, if any.
+    fn insert(&mut self, instructions: &InstructionTable, executed: &[InstructionIndex], instruction_counter: InstructionIndex, registers: &Registers) -> Option<usize> {
+        let step = executed.len();
+
+        let mut hasher = DefaultHasher::new();
+        instruction_counter.hash(&mut hasher);
+        registers.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let first = match self.first_seen.get(&hash) {
+            Some(&first) => first,
+            None => {
+                self.first_seen.insert(hash, step);
+                return None;
+            }
+        };
+
+        let candidates = std::iter::once(first)
+            .chain(self.collisions.get(&hash).into_iter().flatten().copied());
+
+        for earlier in candidates {
+            if executed[earlier] == instruction_counter && replay(instructions, &executed[..earlier]) == *registers {
+                return Some(earlier);
+            }
+        }
+
+        self.collisions.entry(hash).or_default().push(step);
+
+        None
+    }
+}
+
+/// The registers after executing the given instructions in order, starting from the initial state.
+fn replay(instructions: &InstructionTable, executed: &[InstructionIndex]) -> Registers {
+    let mut registers = Registers::new();
+
+    for &instruction_counter in executed {
+        // These instructions already executed successfully once
+        let _ = execute(&instructions[&instruction_counter], instruction_counter, &mut registers);
+    }
+
+    registers
 }
 
 fn run_instructions(instructions: &InstructionTable) -> (bool, Accumulator, CallStack, Coverage) {
+    let (exit, registers, call_stack, coverage) = run_program(instructions, DEFAULT_STEP_LIMIT);
+
+    (exit.is_successful(), read_register(&registers, ACCUMULATOR), call_stack, coverage)
+}
+
+/// Runs until the program terminates, is found to loop forever, fails or has executed `step_limit` instructions.
+///
+/// Unless the program terminated, the last entry of the call stack is the instruction that was not executed.
+fn run_program(instructions: &InstructionTable, step_limit: usize) -> (Exit, Registers, CallStack, Coverage) {
     let mut call_stack = CallStack::new();
     let mut coverage = Coverage::new();
-    let mut instruction_counter: InstructionIndex = call_stack.last().unwrap_or(&0).clone();
+    let mut instruction_counter: InstructionIndex = *call_stack.last().unwrap_or(&0);
+
+    let mut registers = Registers::new();
+    let exit;
 
-    let mut accumulator = 0;
-    let successful;
+    let compare_states = has_conditional_jumps(instructions);
+    let mut states = StateHistory::new();
 
     'run: loop {
-        // println!("before: instruction_counter={0}  accumulator={1}", instruction_counter, accumulator);
+        // println!("before: instruction_counter={0}  registers={1:?}", instruction_counter, registers);
 
         if instruction_counter >= instructions.len() {
-            successful = true;
+            exit = Exit::Terminated;
             break 'run;
         }
 
         call_stack.push(instruction_counter);
         *coverage.entry(instruction_counter).or_insert(0) += 1;
 
-        if coverage.get(&instruction_counter).unwrap() > &1 {
-            successful = false;
+        let looping = if compare_states {
+            states.insert(instructions, &call_stack[..call_stack.len() - 1], instruction_counter, &registers).is_some()
+        } else {
+            coverage[&instruction_counter] > 1
+        };
+
+        if looping {
+            exit = Exit::Loop;
+            break 'run;
+        }
+
+        if call_stack.len() > step_limit {
+            exit = Exit::StepLimit(step_limit);
             break 'run;
         }
 
         let next_instruction = instructions.get(&instruction_counter)
-            .expect(String::from("Invalid instruction counter: ").add(&instruction_counter.to_string()).as_str());
+            .unwrap_or_else(|| panic!("Invalid instruction counter: {0}", instruction_counter));
 
         // println!("instruction={:?}", next_instruction);
 
-        match next_instruction.opcode {
-            NOP => {
-                instruction_counter += 1;
-            },
-            ACC => {
-                accumulator = update_accumulator(accumulator, next_instruction.target);
-                instruction_counter += 1;
-            },
-            JMP => {
-                instruction_counter = update_instruction_counter(instruction_counter, next_instruction.target);
-            },
+        match execute(next_instruction, instruction_counter, &mut registers) {
+            Ok(Some(next_instruction_counter)) => instruction_counter = next_instruction_counter,
+            Ok(None) => {
+                exit = Exit::Terminated;
+                break 'run;
+            }
+            Err(error) => {
+                exit = Exit::Fault(error);
+                break 'run;
+            }
         }
 
-        // println!("after: instruction_counter={0}  accumulator={1}", instruction_counter, accumulator);
+        // println!("after: instruction_counter={0}  registers={1:?}", instruction_counter, registers);
     }
 
-    // println!("run finished: successful={0} instruction_counter={1} accumulator={2}", successful, instruction_counter, accumulator);
+    // println!("run finished: exit={0:?} instruction_counter={1} registers={2:?}", exit, instruction_counter, registers);
     // println!("call_stack={0:?}", call_stack);
     // println!("coverage={0:?}", coverage);
 
-    (successful, accumulator, call_stack, coverage)
+    (exit, registers, call_stack, coverage)
 }
 
 fn part1(instructions: &InstructionTable) {
-    let (successful, accumulator, _call_stack, _coverage) = run_instructions(&instructions);
+    let (successful, accumulator, call_stack, _coverage) = run_instructions(instructions);
 
     println!("part1: successful={0} accumulator={1}", successful, accumulator);
-    println!("part1: call_stack={0:?}", _call_stack);
-    println!("part1: coverage={0:?}", _coverage);
+
+    for line in Profile::from(&call_stack, successful).summary() {
+        println!("part1: {0}", line);
+    }
 }
 
 fn part2(original_instructions: &InstructionTable) {
+    let flipped_index = match ControlFlowGraph::from(original_instructions).find_flip(original_instructions) {
+        Some(flipped_index) => flipped_index,
+        None => {
+            println!("part2: no single NOP/JMP flip makes the program terminate");
+            return;
+        }
+    };
+
     let mut instructions = original_instructions.clone();
-    let mut accumulator: Accumulator = 0;
-    let mut successful = false;
+    instructions.entry(flipped_index)
+        .and_modify(|instruction| *instruction = instruction.flipped().unwrap());
 
-    let mut already_switched: HashSet<InstructionIndex> = HashSet::new();
+    let (successful, accumulator, call_stack, _coverage) = run_instructions(&instructions);
 
-    let mut _call_stack = CallStack::new();
-    let mut _coverage = Coverage::new();
+    println!("part2: flipped={0:?} at {1}", original_instructions[&flipped_index].opcode, flipped_index);
+    println!("part2: successful={0} accumulator={1}", successful, accumulator);
 
-    while !successful {
-        let current_execution = run_instructions(&instructions);
+    for line in Profile::from(&call_stack, successful).summary() {
+        println!("part2: {0}", line);
+    }
+}
 
-        successful = current_execution.0;
-        accumulator = current_execution.1;
-        _call_stack = current_execution.2;
-        _coverage = current_execution.3;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // println!("part2: successful={0} accumulator={1}", successful, accumulator);
-        // println!("part2: call_stack={0:?}", _call_stack);
-        // println!("part2: coverage={0:?}", _coverage);
+    pub(crate) fn program(lines: &[&str]) -> InstructionTable {
+        let input: Vec<String> = lines.iter()
+            .map(|line| String::from(*line))
+            .collect();
 
-        if !successful {
-            instructions = original_instructions.clone();
+        parse_instructions(&input)
+    }
 
-            if let Some((&instruction_counter, instruction)) = &original_instructions.into_iter()
-                .filter(|(instruction_counter, _)| !already_switched.contains(&instruction_counter))
-                .nth(0) {
-                // println!("changing {0}={1:?}", instruction_counter, instruction);
+    pub(crate) fn example1() -> InstructionTable {
+        program(&["nop +0", "acc +1", "jmp +4", "acc +3", "jmp -3", "acc -99", "acc +1", "jmp -4", "acc +6"])
+    }
 
-                let entry = instructions.entry(instruction_counter);
+    #[test]
+    fn run_program_stops_on_overflow() {
+        let (exit, registers, call_stack, _coverage) = run_program(&program(&["set a 9223372036854775807", "add a 1", "halt"]), DEFAULT_STEP_LIMIT);
 
-                match instruction.opcode {
-                    NOP => entry.and_modify(|i| i.opcode = JMP),
-                    JMP => entry.and_modify(|i| i.opcode = NOP),
-                    _ => entry,
-                };
+        assert_eq!(exit, Exit::Fault(String::from("Overflow in a: 9223372036854775807 add 1")));
+        assert_eq!(read_register(&registers, "a"), i64::MAX);
+        assert_eq!(call_stack, vec![0, 1]);
 
-                already_switched.insert(instruction_counter.to_owned());
-            }
-        }
+        let (exit, _registers, _call_stack, _coverage) = run_program(&program(&["set acc -9223372036854775807", "acc -1", "acc -1"]), DEFAULT_STEP_LIMIT);
+
+        assert_eq!(exit, Exit::Fault(String::from("Overflow in acc: -9223372036854775808 -1")));
+        assert!(!run_program(&program(&["set a 4294967296", "mul a a"]), DEFAULT_STEP_LIMIT).0.is_successful());
     }
 
-    println!("part2: successful={0} accumulator={1}", successful, accumulator);
-    println!("part2: call_stack={0:?}", _call_stack);
-    println!("part2: coverage={0:?}", _coverage);
+    #[test]
+    fn run_program_stops_on_jumps_before_start() {
+        let (exit, _registers, _call_stack, _coverage) = run_program(&program(&["nop +0", "jmp -3"]), DEFAULT_STEP_LIMIT);
+
+        assert_eq!(exit, Exit::Fault(String::from("Jump from 1 by -3 leaves the program")));
+
+        let (exit, _registers, _call_stack, _coverage) = run_program(&program(&["jz a -5"]), DEFAULT_STEP_LIMIT);
+
+        assert_eq!(exit, Exit::Fault(String::from("Jump from 0 by -5 leaves the program")));
+    }
+
+    #[test]
+    fn run_program_stops_at_step_limit() {
+        // Counts up forever without repeating a state
+        let (exit, registers, call_stack, _coverage) = run_program(&program(&["set a 1", "add a 1", "jnz a -1"]), 1000);
+
+        assert_eq!(exit, Exit::StepLimit(1000));
+        assert_eq!(exit.to_string(), "step limit of 1000 reached");
+        assert_eq!(call_stack.len(), 1001);
+        assert_eq!(read_register(&registers, "a"), 501);
+    }
+
+    #[test]
+    fn state_history_finds_repeated_state() {
+        // Counts a down to zero and back up again, returning to ip 1 with the same registers
+        let instructions = program(&["set a 1", "add a -1", "jz a +2", "jmp -2", "add a 1", "jmp -4"]);
+        let (exit, registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+        assert_eq!(exit, Exit::Loop);
+        assert_eq!(call_stack, vec![0, 1, 2, 4, 5, 1]);
+        assert_eq!(read_register(&registers, "a"), 1);
+
+        let mut states = StateHistory::new();
+        let mut registers = Registers::new();
+
+        for (step, &instruction_counter) in call_stack.iter().enumerate() {
+            let earlier = states.insert(&instructions, &call_stack[..step], instruction_counter, &registers);
+
+            assert_eq!(earlier, if step == 5 { Some(1) } else { None });
+
+            execute(&instructions[&instruction_counter], instruction_counter, &mut registers).unwrap();
+        }
+    }
 }
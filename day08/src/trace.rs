@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{Accumulator, ACCUMULATOR, CallStack, execute, Instruction, InstructionIndex, InstructionTable, read_register, Registers};
+
+/// One executed instruction.
+#[derive(Debug, PartialEq)]
+pub struct TraceRecord<'a> {
+    pub step: usize,
+    pub ip: InstructionIndex,
+    pub instruction: &'a Instruction,
+    pub acc_before: Accumulator,
+    pub acc_after: Accumulator,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    /// One JSON object per line
+    JsonLines,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("Unknown trace format: {0} (expected jsonl or csv)", s)),
+        }
+    }
+}
+
+/// The instructions that were actually executed: when a run does not terminate, the last entry of the call stack is the
+/// instruction that would have repeated a state, gone past the step limit or failed.
+fn executed(call_stack: &CallStack, successful: bool) -> &[InstructionIndex] {
+    if successful {
+        call_stack
+    } else {
+        &call_stack[..call_stack.len().saturating_sub(1)]
+    }
+}
+
+/// Replays a run from its call stack, recording the accumulator around every executed instruction.
+pub fn trace<'a>(instructions: &'a InstructionTable, call_stack: &CallStack, successful: bool) -> Vec<TraceRecord<'a>> {
+    let mut registers = Registers::new();
+
+    executed(call_stack, successful).iter()
+        .enumerate()
+        .map(|(step, &ip)| {
+            let instruction = &instructions[&ip];
+            let acc_before = read_register(&registers, ACCUMULATOR);
+
+            // Only instructions that were executed successfully are replayed
+            let _ = execute(instruction, ip, &mut registers);
+
+            TraceRecord {
+                step,
+                ip,
+                instruction,
+                acc_before,
+                acc_after: read_register(&registers, ACCUMULATOR),
+            }
+        })
+        .collect()
+}
+
+pub fn write_trace<W: Write>(records: &[TraceRecord], format: &Format, mut output: W) -> io::Result<()> {
+    if *format == Format::Csv {
+        writeln!(output, "step,ip,opcode,operand,acc_before,acc_after")?;
+    }
+
+    for record in records {
+        let opcode = record.instruction.opcode.mnemonic();
+        let operand = record.instruction.operands();
+
+        match format {
+            Format::JsonLines => writeln!(
+                output, "{{\"step\":{0},\"ip\":{1},\"opcode\":\"{2}\",\"operand\":\"{3}\",\"acc_before\":{4},\"acc_after\":{5}}}",
+                record.step, record.ip, opcode, operand, record.acc_before, record.acc_after
+            )?,
+            Format::Csv => writeln!(
+                output, "{0},{1},{2},{3},{4},{5}",
+                record.step, record.ip, opcode, operand, record.acc_before, record.acc_after
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Where a run spent its time.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    pub steps: usize,
+    /// How often each executed instruction ran
+    pub hits: BTreeMap<InstructionIndex, usize>,
+    /// The backward jump taken most often: (jump index, target index, times taken)
+    pub hottest_loop: Option<(InstructionIndex, InstructionIndex, usize)>,
+    /// The first instruction reached a second time, and the step at which that happened
+    pub first_repeat: Option<(InstructionIndex, usize)>,
+}
+
+impl Profile {
+    pub fn from(call_stack: &CallStack, successful: bool) -> Self {
+        let executed = executed(call_stack, successful);
+
+        let mut hits: BTreeMap<InstructionIndex, usize> = BTreeMap::new();
+
+        for &ip in executed {
+            *hits.entry(ip).or_insert(0) += 1;
+        }
+
+        // Every transition in the call stack starts at an executed instruction, including the one into a repeat
+        let mut backward_jumps: BTreeMap<(InstructionIndex, InstructionIndex), usize> = BTreeMap::new();
+
+        for transition in call_stack.windows(2) {
+            if transition[1] <= transition[0] {
+                *backward_jumps.entry((transition[0], transition[1])).or_insert(0) += 1;
+            }
+        }
+
+        // Ties go to the jump with the lowest index
+        let hottest_loop = backward_jumps.into_iter()
+            .fold(None, |hottest: Option<(InstructionIndex, InstructionIndex, usize)>, ((from, to), count)| match hottest {
+                Some((_, _, hottest_count)) if hottest_count >= count => hottest,
+                _ => Some((from, to, count)),
+            });
+
+        let mut first_seen: HashMap<InstructionIndex, usize> = HashMap::new();
+        let first_repeat = call_stack.iter()
+            .enumerate()
+            .find(|&(step, &ip)| *first_seen.entry(ip).or_insert(step) != step)
+            .map(|(step, &ip)| (ip, step));
+
+        Self {
+            steps: executed.len(),
+            hits,
+            hottest_loop,
+            first_repeat,
+        }
+    }
+
+    /// A one-line-per-fact summary without the hit counts.
+    pub fn summary(&self) -> Vec<String> {
+        let mut summary = vec![format!("steps={0} distinct={1}", self.steps, self.hits.len())];
+
+        match self.first_repeat {
+            Some((ip, step)) => summary.push(format!("first repeated instruction={0} at step {1}", ip, step)),
+            None => summary.push(String::from("first repeated instruction=none")),
+        }
+
+        match self.hottest_loop {
+            Some((from, to, count)) => summary.push(format!("hottest loop={0}..={1} taken {2} times", to, from, count)),
+            None => summary.push(String::from("hottest loop=none")),
+        }
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_STEP_LIMIT, run_program};
+    use crate::tests::{example1, program};
+
+    #[test]
+    fn trace_stops_before_repeated_instruction() {
+        let instructions = example1();
+        let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+        let records = trace(&instructions, &call_stack, exit.is_successful());
+
+        assert_eq!(records.len(), 7);
+        assert_eq!(records[3], TraceRecord { step: 3, ip: 6, instruction: &instructions[&6], acc_before: 1, acc_after: 2 });
+        assert_eq!((records[6].ip, records[6].acc_before, records[6].acc_after), (4, 5, 5));
+    }
+
+    #[test]
+    fn write_trace_formats() {
+        let instructions = example1();
+        let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+        let records = trace(&instructions, &call_stack, exit.is_successful());
+
+        let mut csv: Vec<u8> = Vec::new();
+        write_trace(&records[..2], &Format::Csv, &mut csv).unwrap();
+
+        assert_eq!(String::from_utf8(csv).unwrap(), "step,ip,opcode,operand,acc_before,acc_after\n0,0,nop,+0,0,0\n1,1,acc,+1,0,1\n");
+
+        let mut json: Vec<u8> = Vec::new();
+        write_trace(&records[1..2], &Format::JsonLines, &mut json).unwrap();
+
+        assert_eq!(String::from_utf8(json).unwrap(), "{\"step\":1,\"ip\":1,\"opcode\":\"acc\",\"operand\":\"+1\",\"acc_before\":0,\"acc_after\":1}\n");
+    }
+
+    #[test]
+    fn profile_example1() {
+        let instructions = example1();
+        let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+        let profile = Profile::from(&call_stack, exit.is_successful());
+
+        assert_eq!(profile.steps, 7);
+        assert_eq!(profile.hits.values().sum::<usize>(), 7);
+        assert_eq!(profile.first_repeat, Some((1, 7)));
+        assert_eq!(profile.hottest_loop, Some((4, 1, 1)));
+    }
+
+    #[test]
+    fn profile_finds_hottest_loop() {
+        let instructions = program(&["set n 3", "add acc 1", "add n -1", "jnz n -2", "halt"]);
+        let (exit, _registers, call_stack, _coverage) = run_program(&instructions, DEFAULT_STEP_LIMIT);
+
+        let profile = Profile::from(&call_stack, exit.is_successful());
+
+        assert_eq!(profile.hottest_loop, Some((3, 1, 2)));
+        assert_eq!(profile.hits[&1], 3);
+        assert_eq!(profile.summary(), vec![
+            "steps=11 distinct=5",
+            "first repeated instruction=1 at step 4",
+            "hottest loop=1..=3 taken 2 times",
+        ]);
+    }
+}
@@ -1,113 +1,120 @@
+use std::env;
 use std::io::{BufRead, stdin};
-use std::ops::{Add, Range};
+use std::ops::Range;
+use std::process;
 
-type NumberType = u64;
+use crate::xmas::{find_weaknesses, invalid_numbers, Overflow, Wide};
 
-fn main() {
-    let input: Vec<String> = stdin().lock().lines()
-        .map(|maybe_line| maybe_line.expect("Error while reading line"))
-        .collect();
-
-    let numbers: Vec<NumberType> = input.into_iter()
-        .map(|line| line.parse::<NumberType>()
-            .expect(String::from("Could not parse as number: ").add(&line).as_str())
-        )
-        .collect();
-
-    if let Some(part1_result) = part1(&numbers) {
-        let magic_number = part1_result.0;
-
-        println!("Did not find a number pair that sums to {0}", magic_number);
-
-        let windows = part2(&numbers, magic_number);
-
-        for window in windows {
-            let slice = &numbers[window.start..window.end];
-            let min_value = slice.iter().min().unwrap();
-            let max_value = slice.iter().max().unwrap();
-
-            println!("part2: {0:?} sums to {1}; sum(min={2}, max={3})={4}",
-                     slice, magic_number, min_value, max_value, min_value + max_value
-            );
-        }
-    }
-}
-
-fn part1(numbers: &[NumberType]) -> Option<(NumberType, usize, usize)> {
-    process_all_numbers(&numbers, 25, &sum_any_two)
-}
+mod xmas;
 
-fn part2(numbers: &[NumberType], magic_number: NumberType) -> Vec<Range<usize>> {
-    let mut windows: Vec<Range<usize>> = Vec::new();
+/// Wide enough for any unsigned or signed 64-bit input
+type NumberType = i128;
 
-    let length = numbers.len();
+const DEFAULT_PREAMBLE: usize = 25;
 
-    'search: for window_size in (2..=length / 2).rev() {
-        println!("window_size={0}", window_size);
-        for left in 0..length - window_size {
-            let right = left + window_size;
-
-            if right >= length {
-                continue 'search;
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let (preamble, command) = match args.as_slice() {
+        ["--preamble", preamble, command @ ..] => match preamble.parse::<usize>() {
+            Ok(preamble) if preamble >= 2 => (preamble, command),
+            _ => exit_with_usage(&format!("Invalid preamble: {0} (expected a number of at least 2)", preamble)),
+        },
+        command => (DEFAULT_PREAMBLE, command),
+    };
+
+    let lines = stdin().lock().lines()
+        .map(|maybe_line| maybe_line.expect("Error while reading line"));
+
+    match command {
+        [] => {
+            let numbers: Vec<NumberType> = lines
+                .map(|line| parse_number(&line))
+                .collect();
+
+            if let Some((_index, magic_number)) = part1(&numbers, preamble) {
+                println!("Did not find a number pair that sums to {0}", magic_number);
+
+                let weaknesses = part2(&numbers, magic_number).unwrap_or_else(|error| {
+                    eprintln!("{0}", error);
+                    process::exit(1);
+                });
+
+                for (range, weakness) in weaknesses {
+                    let slice = &numbers[range.clone()];
+                    let min_value = slice.iter().min().unwrap();
+                    let max_value = slice.iter().max().unwrap();
+
+                    println!("part2: {0:?} sums to {1}; sum(min={2}, max={3})={4}",
+                             slice, magic_number, min_value, max_value, weakness
+                    );
+                }
             }
-
-            let slice = &numbers[left..right];
-            let sum: NumberType = slice.iter().sum();
-
-            // println!("sum={0} numbers[{1}..{2}]={3:?}", sum, left, right, slice);
-
-            if sum == magic_number {
-                windows.push(left..right);
-                // break 'search;
+        }
+        ["invalid"] => {
+            // Streams the input: every invalid number is printed as soon as it has been read
+            for (index, number) in invalid_numbers(lines.map(|line| parse_number(&line)), preamble) {
+                println!("invalid: index={0} number={1}", index, number);
             }
         }
+        _ => exit_with_usage(&format!("Unknown command: {0}", command.join(" "))),
     }
-
-    windows
 }
 
-fn process_all_numbers(numbers: &[u64], window_size: usize, condition: &dyn Fn(&[NumberType], NumberType) -> Vec<NumberType>) -> Option<(NumberType, usize, usize)> {
-    let mut left = 0;
-    let mut right = window_size;
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day09 [--preamble <n>] [invalid] < input");
+    process::exit(1);
+}
 
-    while right <= numbers.len() {
-        let next_index = right + 1;
+fn parse_number(line: &str) -> NumberType {
+    line.parse::<NumberType>()
+        .unwrap_or_else(|_| panic!("Could not parse as number: {0}", line))
+}
 
-        if next_index >= numbers.len() {
-            panic!("No more numbers at index {0}", next_index);
-        }
+fn part1(numbers: &[NumberType], preamble: usize) -> Option<(usize, NumberType)> {
+    invalid_numbers(numbers.iter().copied(), preamble).next()
+}
 
-        let candidates = &numbers[left..=right];
-        let next_number = numbers[next_index];
-        let operands = condition(candidates, next_number);
+fn part2(numbers: &[NumberType], magic_number: NumberType) -> Result<Vec<(Range<usize>, Wide)>, Overflow> {
+    find_weaknesses(numbers, magic_number)
+}
 
-        if operands.is_empty() {
-            // println!("numbers[{0}..{1}]={2:?}", left, right, candidates);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            return Some((next_number, left, right));
-        }
+    const EXAMPLE: [NumberType; 20] = [35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576];
 
-        left += 1;
-        right = left + window_size;
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&EXAMPLE, 5), Some((14, 127)));
     }
 
-    None
-}
+    fn bundled_input() -> Vec<NumberType> {
+        include_str!("../resources/input").lines()
+            .map(parse_number)
+            .collect()
+    }
 
-fn sum_any_two(numbers: &[NumberType], needle: NumberType) -> Vec<NumberType> {
-    for index in 0..numbers.len() {
-        let left = numbers[index];
+    #[test]
+    fn part1_bundled_input() {
+        assert_eq!(part1(&bundled_input(), DEFAULT_PREAMBLE).map(|(_index, number)| number), Some(507622668));
+    }
 
-        if left >= needle {
-            continue;
-        }
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&EXAMPLE, 127), Ok(vec![(2..6, 62)]));
+    }
 
-        let needle = needle - left;
+    #[test]
+    fn part2_bundled_input() {
+        let numbers = bundled_input();
+        let weaknesses = part2(&numbers, 507622668).unwrap();
 
-        if numbers[index..].contains(&needle) {
-            return vec![left, needle];
-        }
+        assert_eq!(weaknesses.len(), 1);
+        assert_eq!(weaknesses[0].1, 76688505);
+        assert_eq!(numbers[weaknesses[0].0.clone()].len(), 17);
     }
-
-    vec![]
 }
@@ -0,0 +1,207 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Range;
+
+/// Sums of ranges and encryption weaknesses are computed in this type, so they are exact for any 64-bit input.
+pub type Wide = i128;
+
+/// A number type the XMAS analysis can work on.
+pub trait XmasNumber: Copy + Eq + Hash + Ord {
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn widen(self) -> Wide;
+}
+
+macro_rules! xmas_number {
+    ($($type:ty),*) => {
+        $(
+            impl XmasNumber for $type {
+                fn checked_sub(self, other: Self) -> Option<Self> {
+                    <$type>::checked_sub(self, other)
+                }
+
+                fn widen(self) -> Wide {
+                    self as Wide
+                }
+            }
+        )*
+    };
+}
+
+xmas_number!(u32, u64, i32, i64, i128);
+
+/// A sum left the range of `Wide`; only possible with `i128` input.
+#[derive(Debug, PartialEq)]
+pub struct Overflow {
+    pub index: usize,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sum overflows at index {0}", self.index)
+    }
+}
+
+/// Checks a stream of numbers against the XMAS rule: every number after the preamble must be the sum of two of the
+/// `preamble` numbers immediately before it. The window is kept as a multiset, so each check is linear in the preamble
+/// and moving the window is constant time.
+pub struct XmasValidator<T: XmasNumber> {
+    preamble: usize,
+    window: VecDeque<T>,
+    counts: HashMap<T, usize>,
+}
+
+impl<T: XmasNumber> XmasValidator<T> {
+    pub fn new(preamble: usize) -> Self {
+        Self {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Whether `number` is the sum of two numbers at different positions of the window.
+    ///
+    /// If `number - left` is not representable (below zero for unsigned types, out of range for signed ones) no
+    /// number in the window can complete the sum.
+    fn is_sum_of_two(&self, number: T) -> bool {
+        self.window.iter().any(|&left| match number.checked_sub(left) {
+            Some(right) if right == left => self.counts.get(&right).copied().unwrap_or(0) >= 2,
+            Some(right) => self.counts.contains_key(&right),
+            None => false,
+        })
+    }
+
+    /// Adds the next number to the stream; returns false if it breaks the rule. The preamble itself is always valid.
+    pub fn push(&mut self, number: T) -> bool {
+        let valid = self.window.len() < self.preamble || self.is_sum_of_two(number);
+
+        self.window.push_back(number);
+        *self.counts.entry(number).or_insert(0) += 1;
+
+        if self.window.len() > self.preamble {
+            let oldest = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&oldest).unwrap();
+
+            *count -= 1;
+
+            if *count == 0 {
+                self.counts.remove(&oldest);
+            }
+        }
+
+        valid
+    }
+}
+
+/// Lazily yields the index and value of every number that breaks the XMAS rule.
+pub fn invalid_numbers<T: XmasNumber, I: IntoIterator<Item=T>>(numbers: I, preamble: usize) -> impl Iterator<Item=(usize, T)> {
+    let mut validator = XmasValidator::new(preamble);
+
+    numbers.into_iter()
+        .enumerate()
+        .filter(move |&(_index, number)| !validator.push(number))
+}
+
+/// Every contiguous run of at least two numbers that sums to `target`, ordered by start and then end.
+///
+/// Uses prefix sums: `numbers[start..end]` sums to `target` iff `prefix[end] - prefix[start] == target`, so remembering
+/// where each prefix sum occurred finds all runs in a single pass (linear apart from the size of the result). Unlike a
+/// two-pointer scan this does not rely on the sums growing with the run, so negative numbers and zeros are handled.
+pub fn contiguous_ranges<T: XmasNumber>(numbers: &[T], target: T) -> Result<Vec<Range<usize>>, Overflow> {
+    let target = target.widen();
+
+    let mut starts: HashMap<Wide, Vec<usize>> = HashMap::new();
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut prefix: Wide = 0;
+
+    starts.entry(prefix).or_default().push(0);
+
+    for (index, number) in numbers.iter().enumerate() {
+        let end = index + 1;
+
+        prefix = prefix.checked_add(number.widen()).ok_or(Overflow { index })?;
+
+        if let Some(candidates) = prefix.checked_sub(target).and_then(|start_prefix| starts.get(&start_prefix)) {
+            ranges.extend(candidates.iter()
+                .filter(|&&start| end - start >= 2)
+                .map(|&start| start..end)
+            );
+        }
+
+        starts.entry(prefix).or_default().push(end);
+    }
+
+    ranges.sort_by_key(|range| (range.start, range.end));
+
+    Ok(ranges)
+}
+
+/// The encryption weakness of a run: the sum of its smallest and largest number.
+pub fn weakness<T: XmasNumber>(numbers: &[T], range: &Range<usize>) -> Result<Wide, Overflow> {
+    let slice = &numbers[range.clone()];
+
+    slice.iter().min().unwrap().widen()
+        .checked_add(slice.iter().max().unwrap().widen())
+        .ok_or(Overflow { index: range.start })
+}
+
+/// Every run summing to `target` together with its encryption weakness.
+pub fn find_weaknesses<T: XmasNumber>(numbers: &[T], target: T) -> Result<Vec<(Range<usize>, Wide)>, Overflow> {
+    contiguous_ranges(numbers, target)?.into_iter()
+        .map(|range| weakness(numbers, &range).map(|weakness| (range, weakness)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_numbers_reports_all_with_indices() {
+        let invalid: Vec<(usize, u64)> = invalid_numbers(vec![1, 2, 3, 9, 5, 6], 2).collect();
+
+        assert_eq!(invalid, vec![(3, 9), (4, 5), (5, 6)]);
+    }
+
+    #[test]
+    fn sum_needs_two_different_positions() {
+        // 10 = 5 + 5 only counts once 5 appears twice in the window
+        let invalid: Vec<(usize, u64)> = invalid_numbers(vec![5, 5, 10, 5, 1, 10], 2).collect();
+
+        assert_eq!(invalid, vec![(3, 5), (4, 1), (5, 10)]);
+    }
+
+    #[test]
+    fn contiguous_ranges_finds_overlapping_runs_and_zeros() {
+        assert_eq!(contiguous_ranges(&[1u64, 2, 3, 0, 3, 3], 6), Ok(vec![0..3, 0..4, 2..5, 3..6, 4..6]));
+        // A single number equal to the target is not a run
+        assert_eq!(contiguous_ranges(&[6u64, 1], 6), Ok(vec![]));
+    }
+
+    #[test]
+    fn signed_numbers() {
+        let numbers: Vec<i64> = vec![-3, 5, -2, 4, 1, -1, 0];
+
+        assert_eq!(contiguous_ranges(&numbers, 0), Ok(vec![0..3, 4..6, 4..7]));
+        assert_eq!(find_weaknesses(&numbers, 2), Ok(vec![(0..2, 2), (2..4, 2), (2..6, 2), (2..7, 2)]));
+
+        let invalid: Vec<(usize, i64)> = invalid_numbers(vec![-3, 5, 2, -1, 7], 2).collect();
+
+        assert_eq!(invalid, vec![(3, -1), (4, 7)]);
+    }
+
+    #[test]
+    fn extreme_values_do_not_overflow() {
+        let numbers: Vec<u64> = vec![u64::MAX, u64::MAX, 1, u64::MAX - 1];
+
+        assert_eq!(find_weaknesses(&numbers, u64::MAX), Ok(vec![(2..4, u64::MAX as Wide)]));
+
+        // The difference MIN - MAX is not representable; there is simply no partner
+        let invalid: Vec<(usize, i64)> = invalid_numbers(vec![i64::MAX, i64::MIN, i64::MIN], 2).collect();
+
+        assert_eq!(invalid, vec![(2, i64::MIN)]);
+
+        assert_eq!(contiguous_ranges(&[i128::MAX, 1], 0), Err(Overflow { index: 1 }));
+    }
+}
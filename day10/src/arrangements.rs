@@ -0,0 +1,271 @@
+use crate::biguint::BigUint;
+use crate::{Chain, Joltage};
+
+impl Chain {
+    fn device(&self) -> usize {
+        self.joltages.len() - 1
+    }
+
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        self.joltages[to] - self.joltages[from] <= self.max_gap
+    }
+
+    /// For every link the number of arrangements leading from it to the device.
+    ///
+    /// `counts[i]` is the sum of `counts[j]` over every later link `j` within `max_gap`. Those links form a sliding
+    /// window over the sorted chain, so this takes a linear number of big-integer additions.
+    pub fn counts_to_device(&self) -> Vec<BigUint> {
+        let device = self.device();
+
+        let mut counts: Vec<BigUint> = vec![BigUint::zero(); device + 1];
+        counts[device] = BigUint::from(1);
+
+        let mut window_end = device;
+        let mut window_sum = BigUint::from(1);
+
+        for index in (0..device).rev() {
+            while !self.reaches(index, window_end) {
+                window_sum -= &counts[window_end];
+                window_end -= 1;
+            }
+
+            counts[index] = window_sum.clone();
+            window_sum += &counts[index];
+        }
+
+        counts
+    }
+
+    /// The adapters' joltages for a path of link indices.
+    fn arrangement(&self, path: &[usize]) -> Vec<Joltage> {
+        path.iter()
+            .map(|&link| self.joltages[link])
+            .collect()
+    }
+
+    /// Every arrangement (the adapters used, in ascending order) lazily in lexicographic order, where an arrangement
+    /// comes before its extensions. Duplicate adapters count as different adapters, so the same joltages may appear
+    /// more than once.
+    pub fn enumerate(&self) -> Arrangements<'_> {
+        Arrangements {
+            chain: self,
+            path: Vec::new(),
+            started: false,
+        }
+    }
+
+    /// Picks one of the arrangements uniformly at random: at every link the next step is chosen with probability
+    /// proportional to the number of arrangements it leads to.
+    pub fn sample(&self, rng: &mut Rng) -> Vec<Joltage> {
+        let counts = self.counts_to_device();
+        let device = self.device();
+
+        let mut path = Vec::new();
+        let mut link = 0;
+
+        while link != device {
+            let mut choice = rng.below(&counts[link]);
+
+            link = (link + 1..=device)
+                .take_while(|&next| self.reaches(link, next))
+                .find(|&next| {
+                    if choice < counts[next] {
+                        true
+                    } else {
+                        choice -= &counts[next];
+                        false
+                    }
+                })
+                .unwrap();
+
+            if link != device {
+                path.push(link);
+            }
+        }
+
+        self.arrangement(&path)
+    }
+}
+
+pub struct Arrangements<'a> {
+    chain: &'a Chain,
+    /// The link indices of the current arrangement's adapters
+    path: Vec<usize>,
+    started: bool,
+}
+
+impl Arrangements<'_> {
+    fn last_link(&self) -> usize {
+        self.path.last().copied().unwrap_or(0)
+    }
+
+    /// Appends the smallest adapters until the device is in reach.
+    fn extend_minimal(&mut self) {
+        while !self.chain.reaches(self.last_link(), self.chain.device()) {
+            self.path.push(self.last_link() + 1);
+        }
+    }
+
+    /// Moves to the lexicographically next arrangement; returns false after the last one.
+    fn advance(&mut self) -> bool {
+        let device = self.chain.device();
+
+        // The smallest extension: the very next adapter (consecutive links are always within reach)
+        if self.last_link() + 1 < device {
+            self.path.push(self.last_link() + 1);
+            self.extend_minimal();
+            return true;
+        }
+
+        // Otherwise replace the last adapter that has a larger sibling in reach of its predecessor
+        while let Some(link) = self.path.pop() {
+            let sibling = link + 1;
+
+            if sibling < device && self.chain.reaches(self.last_link(), sibling) {
+                self.path.push(sibling);
+                self.extend_minimal();
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<Joltage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            self.extend_minimal();
+        } else if !self.advance() {
+            return None;
+        }
+
+        Some(self.chain.arrangement(&self.path))
+    }
+}
+
+/// A small xorshift64* generator, good enough for sampling test data; the same seed gives the same samples.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, which the one seed equal to the constant would map to
+        match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => Self(0x9e37_79b9_7f4a_7c15),
+            state => Self(state),
+        }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as u32
+    }
+
+    /// A uniformly distributed number in `0..bound`, by rejecting random numbers of the same bit length.
+    pub fn below(&mut self, bound: &BigUint) -> BigUint {
+        assert!(!bound.is_zero(), "Empty range");
+
+        let bits = bound.bits();
+
+        loop {
+            let mut limbs: Vec<u32> = (0..bits.div_ceil(32)).map(|_| self.next_u32()).collect();
+
+            if !bits.is_multiple_of(32) {
+                *limbs.last_mut().unwrap() &= (1 << (bits % 32)) - 1;
+            }
+
+            let candidate = BigUint::from_limbs(limbs);
+
+            if candidate < *bound {
+                return candidate;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChainConfig;
+    use std::collections::{HashMap, HashSet};
+
+    fn chain(adapters: &[Joltage]) -> Chain {
+        Chain::new(adapters, ChainConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn enumerate_example1_in_lexicographic_order() {
+        let arrangements: Vec<Vec<Joltage>> = chain(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4]).enumerate().collect();
+
+        assert_eq!(arrangements.len(), 8);
+        assert_eq!(arrangements[0], vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        assert_eq!(arrangements[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+        assert!(arrangements.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn enumerate_puts_prefixes_first() {
+        // With the device at 3 it can plug straight into the outlet
+        let config = ChainConfig { max_gap: 3, device_offset: 1 };
+        let arrangements: Vec<Vec<Joltage>> = Chain::new(&[1, 2], config).unwrap().enumerate().collect();
+
+        assert_eq!(arrangements, vec![vec![], vec![1], vec![1, 2], vec![2]]);
+
+        // So it does without any adapters
+        assert_eq!(chain(&[]).enumerate().collect::<Vec<_>>(), vec![Vec::<Joltage>::new()]);
+    }
+
+    #[test]
+    fn enumerate_is_lazy() {
+        // Far too many arrangements to walk them all
+        let adapters: Vec<Joltage> = (1..=50).collect();
+        let first: Vec<Vec<Joltage>> = chain(&adapters).enumerate().take(2).collect();
+
+        assert_eq!(first[0], (1..=50).collect::<Vec<_>>());
+        assert_eq!(first[1], (1..=48).chain(50..=50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn counts_exceed_u64() {
+        let adapters: Vec<Joltage> = (1..=100).collect();
+        let counts = chain(&adapters).counts_to_device();
+
+        // A tribonacci number
+        assert_eq!(counts[0].to_string(), "180396380815100901214157639");
+    }
+
+    #[test]
+    fn sample_is_valid_and_covers_all_arrangements() {
+        let chain = chain(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4]);
+        let mut rng = Rng::new(42);
+        let mut seen: HashMap<Vec<Joltage>, usize> = HashMap::new();
+
+        for _ in 0..800 {
+            *seen.entry(chain.sample(&mut rng)).or_insert(0) += 1;
+        }
+
+        let all: Vec<Vec<Joltage>> = chain.enumerate().collect();
+
+        assert_eq!(seen.len(), all.len());
+        assert!(seen.keys().all(|arrangement| all.contains(arrangement)));
+        assert!(seen.values().all(|&count| count > 60 && count < 140));
+    }
+
+    #[test]
+    fn sample_varies_for_every_seed() {
+        let chain = chain(&[1, 2, 3, 4, 5, 6]);
+
+        for seed in &[0, 1, 0x9e37_79b9_7f4a_7c15] {
+            let mut rng = Rng::new(*seed);
+            let samples: HashSet<Vec<Joltage>> = (0..20).map(|_| chain.sample(&mut rng)).collect();
+
+            assert!(samples.len() > 1, "seed {0} always samples {1:?}", seed, samples);
+        }
+    }
+}
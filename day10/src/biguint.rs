@@ -0,0 +1,178 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{AddAssign, SubAssign};
+
+/// An arbitrary-precision unsigned integer, just large enough for counting arrangements: addition, subtraction,
+/// comparison and decimal printing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigUint {
+    /// Base 2^32 digits, least significant first, without leading zeros (zero has none)
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self { limbs: Vec::new() }
+    }
+
+    pub fn from_limbs(limbs: Vec<u32>) -> Self {
+        let mut number = Self { limbs };
+        number.normalize();
+        number
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// The number of significant bits.
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(most_significant) => self.limbs.len() * 32 - most_significant.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    /// Divides in place by a small divisor and returns the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder: u64 = 0;
+
+        for limb in self.limbs.iter_mut().rev() {
+            let current = (remainder << 32) | *limb as u64;
+            *limb = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+
+        self.normalize();
+
+        remainder as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self::from_limbs(vec![value as u32, (value >> 32) as u32])
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+
+        let mut carry = 0u64;
+
+        for (index, limb) in self.limbs.iter_mut().enumerate() {
+            let sum = *limb as u64 + other.limbs.get(index).copied().unwrap_or(0) as u64 + carry;
+            *limb = sum as u32;
+            carry = sum >> 32;
+        }
+
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl SubAssign<&BigUint> for BigUint {
+    /// Panics if `other` is larger, as the result would be negative.
+    fn sub_assign(&mut self, other: &BigUint) {
+        assert!(*self >= *other, "BigUint subtraction underflow");
+
+        let mut borrow = 0i64;
+
+        for (index, limb) in self.limbs.iter_mut().enumerate() {
+            let mut difference = *limb as i64 - other.limbs.get(index).copied().unwrap_or(0) as i64 - borrow;
+
+            borrow = if difference < 0 {
+                difference += 1 << 32;
+                1
+            } else {
+                0
+            };
+
+            *limb = difference as u32;
+        }
+
+        self.normalize();
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Nine decimal digits at a time, least significant chunk first
+        let mut remaining = self.clone();
+        let mut chunks: Vec<u32> = Vec::new();
+
+        while !remaining.is_zero() {
+            chunks.push(remaining.div_rem_small(1_000_000_000));
+        }
+
+        write!(f, "{0}", chunks.last().unwrap())?;
+
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{0:09}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_subtract_carry_across_limbs() {
+        let mut number = BigUint::from(u64::MAX);
+        number += &BigUint::from(1);
+
+        assert_eq!(number, BigUint::from_limbs(vec![0, 0, 1]));
+        assert_eq!(number.to_string(), "18446744073709551616");
+        assert_eq!(number.bits(), 65);
+
+        number -= &BigUint::from(1);
+
+        assert_eq!(number, BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    fn display_pads_inner_chunks() {
+        let mut number = BigUint::from(1_000_000_000_000_000_000);
+        number += &BigUint::from(7);
+
+        assert_eq!(number.to_string(), "1000000000000000007");
+        assert_eq!(BigUint::zero().to_string(), "0");
+    }
+
+    #[test]
+    fn ordering_compares_magnitude() {
+        assert!(BigUint::from_limbs(vec![0, 1]) > BigUint::from(u32::MAX as u64));
+        assert!(BigUint::from(3) < BigUint::from(4));
+        assert_eq!(BigUint::from_limbs(vec![5, 0, 0]), BigUint::from(5));
+    }
+}
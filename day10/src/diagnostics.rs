@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use crate::{Chain, GapError, Joltage};
+
+/// What the sorted chain looks like, including chains that cannot be completed.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostics {
+    /// For every difference between consecutive links the chain indices of the lower link (0 is the outlet)
+    pub differences: BTreeMap<Joltage, Vec<usize>>,
+    pub gaps: Vec<GapError>,
+    /// Ratings shared by more than one adapter, with the number of adapters
+    pub duplicates: BTreeMap<Joltage, usize>,
+    /// Adapters used by every valid arrangement; `None` if there is no valid arrangement
+    pub mandatory: Option<Vec<Joltage>>,
+    /// Adapters some valid arrangement can do without; `None` if there is no valid arrangement
+    pub optional: Option<Vec<Joltage>>,
+}
+
+impl Diagnostics {
+    pub fn from(chain: &Chain) -> Self {
+        let joltages = &chain.joltages;
+        let device = joltages.len() - 1;
+
+        let mut differences: BTreeMap<Joltage, Vec<usize>> = BTreeMap::new();
+
+        for (index, pair) in joltages.windows(2).enumerate() {
+            differences.entry(pair[1] - pair[0]).or_default().push(index);
+        }
+
+        let mut duplicates: BTreeMap<Joltage, usize> = BTreeMap::new();
+
+        for &adapter in &joltages[1..device] {
+            *duplicates.entry(adapter).or_insert(0) += 1;
+        }
+
+        duplicates.retain(|_adapter, count| *count > 1);
+
+        let gaps = chain.gaps();
+
+        // An adapter can be skipped iff its neighbours are within reach of each other: any wider jump over it spans
+        // at least that much. Only meaningful if every consecutive pair is within reach.
+        let (mandatory, optional) = if gaps.is_empty() {
+            let (mandatory, optional): (Vec<usize>, Vec<usize>) = (1..device)
+                .partition(|&link| joltages[link + 1] - joltages[link - 1] > chain.max_gap);
+
+            (
+                Some(mandatory.into_iter().map(|link| joltages[link]).collect()),
+                Some(optional.into_iter().map(|link| joltages[link]).collect()),
+            )
+        } else {
+            (None, None)
+        };
+
+        Self {
+            differences,
+            gaps,
+            duplicates,
+            mandatory,
+            optional,
+        }
+    }
+
+    pub fn report(&self) -> Vec<String> {
+        let mut report: Vec<String> = Vec::new();
+
+        for (difference, positions) in &self.differences {
+            let positions: Vec<String> = positions.iter().map(usize::to_string).collect();
+            report.push(format!("difference {0}: {1} times, after links {2}", difference, positions.len(), positions.join(", ")));
+        }
+
+        for gap in &self.gaps {
+            report.push(format!("error: {0}", gap));
+        }
+
+        for (adapter, count) in &self.duplicates {
+            report.push(format!("duplicate: {0} adapters rated {1}", count, adapter));
+        }
+
+        match (&self.mandatory, &self.optional) {
+            (Some(mandatory), Some(optional)) => {
+                report.push(format!("mandatory: {0} adapters {1:?}", mandatory.len(), mandatory));
+                report.push(format!("optional: {0} adapters {1:?}", optional.len(), optional));
+            }
+            _ => report.push(String::from("mandatory/optional: no valid arrangement")),
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChainConfig;
+
+    #[test]
+    fn diagnostics_example1() {
+        let chain = Chain::new(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4], ChainConfig::default()).unwrap();
+        let diagnostics = Diagnostics::from(&chain);
+
+        assert_eq!(diagnostics.differences[&1], vec![0, 2, 3, 4, 6, 7, 9]);
+        assert_eq!(diagnostics.differences[&3], vec![1, 5, 8, 10, 11]);
+        assert_eq!(diagnostics.gaps, vec![]);
+        assert_eq!(diagnostics.mandatory, Some(vec![1, 4, 7, 10, 12, 15, 16, 19]));
+        assert_eq!(diagnostics.optional, Some(vec![5, 6, 11]));
+    }
+
+    #[test]
+    fn diagnostics_reports_twos_duplicates_and_gaps() {
+        let diagnostics = Diagnostics::from(&Chain::sorted(&[2, 2, 4, 9], ChainConfig::default()));
+
+        assert_eq!(diagnostics.report(), vec![
+            "difference 0: 1 times, after links 1",
+            "difference 2: 2 times, after links 0, 2",
+            "difference 3: 1 times, after links 4",
+            "difference 5: 1 times, after links 3",
+            "error: Gap of 5 jolts from 4 to 9 exceeds the maximum of 3",
+            "duplicate: 2 adapters rated 2",
+            "mandatory/optional: no valid arrangement",
+        ]);
+    }
+
+    #[test]
+    fn duplicates_are_optional() {
+        let chain = Chain::new(&[2, 2, 4], ChainConfig::default()).unwrap();
+        let diagnostics = Diagnostics::from(&chain);
+
+        assert_eq!(diagnostics.mandatory, Some(vec![4]));
+        assert_eq!(diagnostics.optional, Some(vec![2, 2]));
+    }
+}
@@ -1,9 +1,125 @@
-use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::io::{BufRead, stdin};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::arrangements::Rng;
+use crate::biguint::BigUint;
+use crate::diagnostics::Diagnostics;
+
+mod arrangements;
+mod biguint;
+mod diagnostics;
 
 type Joltage = u32;
 
+/// How far apart two links of the chain may be, and how much higher than the highest adapter the device is rated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChainConfig {
+    max_gap: Joltage,
+    device_offset: Joltage,
+}
+
+impl ChainConfig {
+    /// The rating of your device for the given highest adapter, or `None` if it does not fit into a `Joltage`.
+    fn device_joltage(&self, highest_adapter: Joltage) -> Option<Joltage> {
+        highest_adapter.checked_add(self.device_offset)
+    }
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            max_gap: 3,
+            device_offset: 3,
+        }
+    }
+}
+
+/// Two consecutive links of the sorted chain are further apart than the maximum gap, so no arrangement can use them.
+#[derive(Debug, PartialEq)]
+struct GapError {
+    from: Joltage,
+    to: Joltage,
+    max_gap: Joltage,
+}
+
+impl fmt::Display for GapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gap of {0} jolts from {1} to {2} exceeds the maximum of {3}", self.to - self.from, self.from, self.to, self.max_gap)
+    }
+}
+
+/// The charging outlet (0 jolts), every adapter in ascending order and the device.
+#[derive(Debug, PartialEq)]
+struct Chain {
+    joltages: Vec<Joltage>,
+    max_gap: Joltage,
+}
+
+impl Chain {
+    /// Sorts the adapters into a chain; fails with every gap that is too wide.
+    fn new(adapters: &[Joltage], config: ChainConfig) -> Result<Self, Vec<GapError>> {
+        let chain = Self::sorted(adapters, config);
+        let gaps = chain.gaps();
+
+        if gaps.is_empty() {
+            Ok(chain)
+        } else {
+            Err(gaps)
+        }
+    }
+
+    /// Sorts the adapters into a chain without validating it.
+    ///
+    /// The device joltage must fit into a `Joltage`, see `ChainConfig::device_joltage`.
+    fn sorted(adapters: &[Joltage], config: ChainConfig) -> Self {
+        let mut joltages = adapters.to_vec();
+        joltages.sort_unstable();
+
+        // The charging outlet has an effective rating of 0 jolts
+        joltages.insert(0, 0);
+
+        // your device's built-in adapter is always `device_offset` higher than the highest adapter
+        let device = config.device_joltage(*joltages.last().unwrap())
+            .expect("Device joltage does not fit into a Joltage");
+        joltages.push(device);
+
+        Self { joltages, max_gap: config.max_gap }
+    }
+
+    /// Every pair of consecutive links that is too far apart.
+    fn gaps(&self) -> Vec<GapError> {
+        self.joltages.windows(2)
+            .filter(|pair| pair[1] - pair[0] > self.max_gap)
+            .map(|pair| GapError { from: pair[0], to: pair[1], max_gap: self.max_gap })
+            .collect()
+    }
+
+    /// The differences between consecutive links, ending with the one to the device.
+    fn differences(&self) -> Vec<Joltage> {
+        self.joltages.windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect()
+    }
+
+    /// The number of distinct adapter arrangements connecting the outlet to the device.
+    fn arrangements(&self) -> BigUint {
+        self.counts_to_device().swap_remove(0)
+    }
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // A command and its arguments come before the options
+    let options_start = args.iter()
+        .position(|arg| arg.starts_with("--"))
+        .unwrap_or(args.len());
+    let command: Vec<&str> = args[..options_start].iter().map(String::as_str).collect();
+    let config = parse_config(&args[options_start..]);
+
     let input: Vec<String> = stdin().lock().lines()
         .map(|maybe_line| maybe_line.expect("Error while reading line"))
         .collect();
@@ -13,139 +129,200 @@ fn main() {
     let adapters: Vec<Joltage> = input
         .iter()
         .map(|adapter_string| adapter_string.parse::<Joltage>()
-            .expect(format!("Could not parse as number: {0}", adapter_string).as_str())
+            .unwrap_or_else(|_| panic!("Could not parse as number: {0}", adapter_string))
         )
         .collect();
 
     // println!("adapters={0:?}", adapters);
 
-    let part1_result = part1(&adapters);
+    let highest_adapter = adapters.iter().copied().max().unwrap_or(0);
 
-    if !part1_result.is_empty() {
-        // println!("part1_result={0:?}", part1_result);
+    if config.device_joltage(highest_adapter).is_none() {
+        exit_with_usage(&format!("Device offset of {0} jolts is too large for the highest adapter of {1} jolts", config.device_offset, highest_adapter));
+    }
 
-        let ones = part1_result.iter()
-            .filter(|&difference| *difference == 1)
-            .count();
+    if command == ["report"] {
+        // Reports on invalid chains as well, so this comes before validation
+        for line in Diagnostics::from(&Chain::sorted(&adapters, config)).report() {
+            println!("{0}", line);
+        }
 
-        let threes = part1_result.iter()
-            .filter(|&difference| *difference == 3)
-            .count();
+        return;
+    }
 
-        println!("part1: ones={0} threes={1} product={2}", ones, threes, ones * threes);
+    let chain = Chain::new(&adapters, config).unwrap_or_else(|gaps| {
+        for gap in gaps {
+            eprintln!("{0}", gap);
+        }
 
-        let number_of_combinations = part2(&part1_result);
+        process::exit(1);
+    });
 
-        println!("part2: number_of_combinations={0}", number_of_combinations);
-    }
-}
+    match command.as_slice() {
+        [] => (),
+        ["enumerate"] => {
+            for arrangement in chain.enumerate() {
+                println!("{0:?}", arrangement);
+            }
 
-fn part1(adapters: &Vec<u32>) -> Vec<u32> {
-    let mut adapters = adapters.clone();
-    adapters.sort();
+            return;
+        }
+        ["enumerate", limit] => {
+            let limit = limit.parse::<usize>()
+                .unwrap_or_else(|_| exit_with_usage(&format!("Invalid limit: {0}", limit)));
+
+            for arrangement in chain.enumerate().take(limit) {
+                println!("{0:?}", arrangement);
+            }
 
-    // println!("sorted adapters={0:?}", adapters);
+            return;
+        }
+        ["sample", arguments @ ..] if arguments.len() <= 2 => {
+            let count = match arguments.first() {
+                Some(count) => count.parse::<usize>()
+                    .unwrap_or_else(|_| exit_with_usage(&format!("Invalid count: {0}", count))),
+                None => 1,
+            };
 
-    let mut differences: Vec<u32> = Vec::new();
+            let seed = match arguments.get(1) {
+                Some(seed) => seed.parse::<u64>()
+                    .unwrap_or_else(|_| exit_with_usage(&format!("Invalid seed: {0}", seed))),
+                None => SystemTime::now().duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_nanos() as u64)
+                    .unwrap_or(0),
+            };
 
-    // The charging outlet has an effective rating of 0 jolts
-    let mut last_adapter = 0;
+            let mut rng = Rng::new(seed);
 
-    for current_adapter in adapters {
-        // println!("last={0}  current={1}  diff={2}",
-        //          last_adapter, current_adapter, current_adapter - last_adapter
-        // );
+            for _ in 0..count {
+                println!("{0:?}", chain.sample(&mut rng));
+            }
 
-        differences.push(current_adapter - last_adapter);
-        last_adapter = current_adapter;
+            return;
+        }
+        _ => exit_with_usage(&format!("Unknown command: {0}", command.join(" "))),
     }
 
-    // your device's built-in adapter is always 3 higher than the highest adapter
-    differences.push(3);
+    let part1_result = part1(&chain);
 
-    differences
-}
+    // println!("part1_result={0:?}", part1_result);
 
-fn part2(differences: &Vec<u32>) -> u64 {
-    // println!("#differences={0} -> {1:?}", differences.len(), differences);
+    let ones = part1_result.iter()
+        .filter(|&difference| *difference == 1)
+        .count();
 
-    let mut combinations: u64 = 1;
+    let threes = part1_result.iter()
+        .filter(|&difference| *difference == 3)
+        .count();
 
-    let mut left = 0;
-    let mut cache: HashMap<u64, u64> = HashMap::new();
+    println!("part1: ones={0} threes={1} product={2}", ones, threes, ones * threes);
 
-    while left < differences.len() {
-        let mut right = left;
+    let number_of_combinations = part2(&chain);
 
-        while right < differences.len() && differences.get(right).unwrap() == &1 {
-            right += 1;
-        }
+    println!("part2: number_of_combinations={0}", number_of_combinations);
+}
 
-        let length_of_one_sequence = right - left;
-        combinations *= tribonacci(length_of_one_sequence as u64, &mut cache);
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day10 [enumerate [<limit>]|sample [<count> [<seed>]]|report] [--max-gap <jolts>] [--device-offset <jolts>] < input");
+    process::exit(1);
+}
 
-        while right < differences.len() && differences.get(right).unwrap() == &3 {
-            right += 1;
-        }
+fn parse_config(args: &[String]) -> ChainConfig {
+    let mut config = ChainConfig::default();
 
-        left = right;
+    for option in args.chunks(2) {
+        let value = match option {
+            [_, value] => value.parse::<Joltage>()
+                .unwrap_or_else(|_| exit_with_usage(&format!("Invalid number of jolts: {0}", value))),
+            _ => exit_with_usage(&format!("Missing value for {0}", option[0])),
+        };
+
+        match option[0].as_str() {
+            "--max-gap" => config.max_gap = value,
+            "--device-offset" => config.device_offset = value,
+            unknown => exit_with_usage(&format!("Unknown option: {0}", unknown)),
+        }
     }
 
-    combinations
+    config
 }
 
-// Truth be told, I got this from Reddit
-fn tribonacci(number: u64, cache: &mut HashMap<u64, u64>) -> u64 {
-    // println!("number={0} cache={1:?}", number, cache);
-
-    match cache.get(&number).map(|entry| entry.clone()) {
-        Some(result) => result,
-        None => {
-            let result = match number {
-                0 => 1,
-                1 => 1,
-                2 => 2,
-                n => tribonacci(n - 1, cache)
-                    + tribonacci(n - 2, cache)
-                    + tribonacci(n - 3, cache),
-            };
+fn part1(chain: &Chain) -> Vec<Joltage> {
+    chain.differences()
+}
 
-            cache.insert(number, result);
-            result
-        }
-    }
+fn part2(chain: &Chain) -> BigUint {
+    chain.arrangements()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn part1_example1() {
+    fn example1() -> Chain {
         let adapters: Vec<u32> = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
 
-        let differences = part1(&adapters);
+        Chain::new(&adapters, ChainConfig::default()).unwrap()
+    }
+
+    fn example2() -> Chain {
+        let adapters: Vec<u32> = vec![28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35, 8, 17, 7, 9, 4, 2, 34, 10, 3];
+
+        Chain::new(&adapters, ChainConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn part1_example1() {
+        let differences = part1(&example1());
 
         assert_eq!(differences, vec![1, 3, 1, 1, 1, 3, 1, 1, 3, 1, 3, 3]);
     }
 
     #[test]
     fn part2_example1() {
-        let adapters: Vec<u32> = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
-        let differences = part1(&adapters);
-
-        let can_skip = part2(&differences);
+        let can_skip = part2(&example1());
 
-        assert_eq!(can_skip, 3);
+        assert_eq!(can_skip, BigUint::from(8));
     }
 
     #[test]
     fn part2_example2() {
-        let adapters: Vec<u32> = vec![28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35, 8, 17, 7, 9, 4, 2, 34, 10, 3];
-        let differences = part1(&adapters);
+        let can_skip = part2(&example2());
+
+        assert_eq!(can_skip, BigUint::from(19208));
+    }
+
+    #[test]
+    fn gaps_above_the_tolerance_are_errors() {
+        assert_eq!(Chain::new(&[1, 5, 6, 12], ChainConfig::default()), Err(vec![
+            GapError { from: 1, to: 5, max_gap: 3 },
+            GapError { from: 6, to: 12, max_gap: 3 },
+        ]));
+
+        let config = ChainConfig { max_gap: 3, device_offset: 4 };
+        assert_eq!(Chain::new(&[1], config), Err(vec![GapError { from: 1, to: 5, max_gap: 3 }]));
+    }
+
+    #[test]
+    fn arrangements_with_other_tolerances() {
+        // With a maximum gap of 1 every adapter is mandatory
+        let config = ChainConfig { max_gap: 1, device_offset: 1 };
+        assert_eq!(Chain::new(&[3, 1, 2], config).unwrap().arrangements(), BigUint::from(1));
+
+        // With a maximum gap of 4 and the device at 7 any subset of 1, 2 works, as long as 3 is used
+        let config = ChainConfig { max_gap: 4, device_offset: 4 };
+        assert_eq!(Chain::new(&[3, 1, 2], config).unwrap().arrangements(), BigUint::from(4));
 
-        let can_skip = part2(&differences);
+        // Duplicate adapters are distinct adapters
+        assert_eq!(Chain::new(&[1, 1], ChainConfig::default()).unwrap().arrangements(), BigUint::from(3));
+    }
+
+    #[test]
+    fn device_joltage_reports_overflow() {
+        let config = ChainConfig { max_gap: 3, device_offset: Joltage::MAX - 4 };
 
-        assert_eq!(can_skip, 19208);
+        assert_eq!(config.device_joltage(4), Some(Joltage::MAX));
+        assert_eq!(config.device_joltage(5), None);
     }
 }
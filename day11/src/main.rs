@@ -1,6 +1,18 @@
-use std::io::{BufRead, stdin};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, stdin, stdout, Write};
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
-#[derive(Clone, Debug, PartialEq)]
+use crate::render::{ansi_frame, CLEAR_SCREEN, FrameFormat, write_ppm_frame, write_text_frame};
+use crate::simulation::{Outcome, Simulation};
+
+mod render;
+mod simulation;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum SeatType {
     None,
     Empty,
@@ -9,26 +21,24 @@ enum SeatType {
 
 type GridDimensionType = i32;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct SeatGrid {
     width: GridDimensionType,
     height: GridDimensionType,
     seats: Vec<SeatType>,
 }
 
-type SeatStateMapper = dyn Fn(&SeatGrid, GridDimensionType, GridDimensionType) -> Option<SeatType>;
-
 impl SeatGrid {
-    fn from(lines: &Vec<String>) -> Self {
+    fn from(lines: &[String]) -> Self {
         let width = lines.first()
-            .expect(format!("Cannot convert to SeatGrid from invalid/empty input: {0:?}", lines).as_str())
+            .unwrap_or_else(|| panic!("Cannot convert to SeatGrid from invalid/empty input: {0:?}", lines))
             .len() as GridDimensionType;
 
         let height = lines.len() as GridDimensionType;
 
-        let seats: Vec<SeatType> = lines.into_iter()
+        let seats: Vec<SeatType> = lines.iter()
             .flat_map(|line| line.chars())
-            .map(|char| SeatGrid::char_to_seat_type(char))
+            .map(SeatGrid::char_to_seat_type)
             .collect();
 
         Self {
@@ -43,7 +53,7 @@ impl SeatGrid {
             '.' => SeatType::None,
             'L' => SeatType::Empty,
             '#' => SeatType::Occupied,
-            _ => panic!(format!("Invalid seat type: {0}", char))
+            _ => panic!("Invalid seat type: {0}", char)
         }
     }
 
@@ -68,54 +78,105 @@ impl SeatGrid {
 
         result
     }
+}
 
-    fn is_occupied(&self, x: GridDimensionType, y: GridDimensionType) -> Option<bool> {
-        let result = self.seat_at(x, y)
-            .map(|tile| *tile == SeatType::Occupied);
-
-        // println!("is_occupied({0}, {1})={2:?}", x, y, result);
+/// Which cells around a seat count as its neighbors.
+#[derive(Clone, Debug, PartialEq)]
+enum Neighborhood {
+    /// The eight surrounding cells
+    Moore,
+    /// The four orthogonally adjacent cells
+    VonNeumann,
+    /// The first seat in each of the eight directions, looking at most this many cells far (unlimited if `None`)
+    LineOfSight(Option<GridDimensionType>),
+}
 
-        result
+const MOORE_DIRECTIONS: [(GridDimensionType, GridDimensionType); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+const VON_NEUMANN_DIRECTIONS: [(GridDimensionType, GridDimensionType); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').collect::<Vec<&str>>().as_slice() {
+            ["moore"] => Ok(Neighborhood::Moore),
+            ["von-neumann"] => Ok(Neighborhood::VonNeumann),
+            ["line-of-sight"] => Ok(Neighborhood::LineOfSight(None)),
+            ["line-of-sight", max_distance] => match max_distance.parse::<GridDimensionType>() {
+                Ok(max_distance) if max_distance > 0 => Ok(Neighborhood::LineOfSight(Some(max_distance))),
+                _ => Err(format!("Invalid maximum distance: {0}", max_distance)),
+            },
+            _ => Err(format!("Unknown neighborhood: {0} (expected moore, von-neumann or line-of-sight[:<max distance>])", s)),
+        }
     }
+}
 
-    fn num_adjacent_occupied(&self, x: GridDimensionType, y: GridDimensionType) -> usize {
-        let adjacent_seats = vec![
-            self.is_occupied(x - 1, y - 1),
-            self.is_occupied(x, y - 1),
-            self.is_occupied(x + 1, y - 1),
-            self.is_occupied(x - 1, y),
-            self.is_occupied(x + 1, y),
-            self.is_occupied(x - 1, y + 1),
-            self.is_occupied(x, y + 1),
-            self.is_occupied(x + 1, y + 1),
-        ];
-
-        adjacent_seats.into_iter()
-            .filter(|maybe_seat| maybe_seat.is_some())
-            .map(|maybe_seat| maybe_seat.unwrap())
-            .filter(|&seat_occupied| seat_occupied)
-            .count()
-    }
+/// How seats change from one round to the next.
+#[derive(Clone, Debug, PartialEq)]
+struct Rules {
+    neighborhood: Neighborhood,
+    /// An empty seat becomes occupied if at most this many neighbors are occupied
+    occupy_threshold: usize,
+    /// An occupied seat becomes empty if at least this many neighbors are occupied
+    vacate_threshold: usize,
+}
 
-    fn apply_rules(&self, seat_state_mapper: &SeatStateMapper) -> SeatGrid {
-        let mut after: Vec<SeatType> = Vec::new();
-        after.reserve_exact(self.len());
+impl Rules {
+    fn part1() -> Self {
+        Self {
+            neighborhood: Neighborhood::Moore,
+            occupy_threshold: 0,
+            vacate_threshold: 4,
+        }
+    }
 
-        for y in 0..self.height {
-            for x in 0..self.width {
-                after.push(seat_state_mapper(self, x, y).unwrap());
-            }
+    fn part2() -> Self {
+        Self {
+            neighborhood: Neighborhood::LineOfSight(None),
+            occupy_threshold: 0,
+            vacate_threshold: 5,
         }
+    }
 
-        SeatGrid {
-            width: self.width,
-            height: self.height,
-            seats: after,
+    fn next_state(&self, seat: &SeatType, occupied_neighbors: usize) -> SeatType {
+        match seat {
+            SeatType::Empty if occupied_neighbors <= self.occupy_threshold => SeatType::Occupied,
+            SeatType::Occupied if occupied_neighbors >= self.vacate_threshold => SeatType::Empty,
+            _ => seat.clone(),
         }
     }
 }
 
+impl SeatGrid {
+    /// The indices of the seats counting as neighbors of (x, y). Looking along a line of sight skips floor, so the
+    /// first seat in each direction counts.
+    fn neighbors(&self, x: GridDimensionType, y: GridDimensionType, neighborhood: &Neighborhood) -> Vec<usize> {
+        let (directions, max_distance): (&[(GridDimensionType, GridDimensionType)], Option<GridDimensionType>) = match neighborhood {
+            Neighborhood::Moore => (&MOORE_DIRECTIONS, Some(1)),
+            Neighborhood::VonNeumann => (&VON_NEUMANN_DIRECTIONS, Some(1)),
+            Neighborhood::LineOfSight(max_distance) => (&MOORE_DIRECTIONS, *max_distance),
+        };
+
+        directions.iter()
+            .filter_map(|&(dx, dy)| (1..)
+                .take_while(|&distance| max_distance.map(|max_distance| distance <= max_distance).unwrap_or(true))
+                .map_while(|distance| self.seat_index(x + dx * distance, y + dy * distance))
+                .map(|index| index as usize)
+                .find(|&index| self.seats[index] != SeatType::None)
+            )
+            .collect()
+    }
+
+    fn num_occupied(&self) -> usize {
+        self.seats.iter()
+            .filter(|&seat| *seat == SeatType::Occupied)
+            .count()
+    }
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
     let input: Vec<String> = stdin().lock().lines()
         .map(|maybe_line| maybe_line.expect("Error while reading line"))
         .collect();
@@ -124,145 +185,183 @@ fn main() {
 
     let seat_grid = SeatGrid::from(&input);
 
-    let (part1_grid, part1_occupied_seats) = part1(&seat_grid);
-    println!("part1_occupied_seats={0:?} -> {1:?}", part1_occupied_seats, part1_grid);
+    match args.first().map(String::as_str) {
+        None => {
+            let (part1_grid, part1_occupied_seats) = part1(&seat_grid);
+            println!("part1_occupied_seats={0:?}", part1_occupied_seats);
 
-    let (part2_grid, part2_occupied_seats) = part2(&seat_grid);
-    println!("part2_occupied_seats={0:?} -> {1:?}", part2_occupied_seats, part2_grid);
-}
+            for line in seat_grid_to_input_format(&part1_grid) {
+                println!("{0}", line);
+            }
 
-fn part1_seat_state_mapper(seat_grid: &SeatGrid, x: GridDimensionType, y: GridDimensionType) -> Option<SeatType> {
-    seat_grid.seat_at(x, y)
-        .map(|current_seat| {
-            // println!("seats={3:?} seat({0}, {1})={2:?}", x, y, current_seat, self.seats);
-            match current_seat {
-                SeatType::Empty => {
-                    let adjacent_occupied_seats = seat_grid.num_adjacent_occupied(x, y);
-                    if adjacent_occupied_seats == 0 {
-                        SeatType::Occupied
-                    } else {
-                        current_seat.clone()
-                    }
-                }
-                SeatType::Occupied => {
-                    let adjacent_occupied_seats = seat_grid.num_adjacent_occupied(x, y);
-                    if adjacent_occupied_seats >= 4 {
-                        SeatType::Empty
-                    } else {
-                        current_seat.clone()
-                    }
-                }
-                SeatType::None => SeatType::None,
+            let (part2_grid, part2_occupied_seats) = part2(&seat_grid);
+            println!("part2_occupied_seats={0:?}", part2_occupied_seats);
+
+            for line in seat_grid_to_input_format(&part2_grid) {
+                println!("{0}", line);
             }
-        })
-}
+        }
+        Some("simulate") => {
+            let options = parse_options(&args[1..]).unwrap_or_else(|error| exit_with_usage(&error));
 
-fn part1(seat_grid: &SeatGrid) -> (SeatGrid, usize) {
-    let mut previous_grid: SeatGrid = seat_grid.clone();
+            let (final_grid, outcome) = simulate_until_stable(&seat_grid, &options.rules, options.round_limit);
+
+            print_outcome(&outcome, &final_grid);
+
+            for line in seat_grid_to_input_format(&final_grid) {
+                println!("{0}", line);
+            }
+        }
+        Some("animate") => {
+            let options = parse_options(&args[1..]).unwrap_or_else(|error| exit_with_usage(&error));
 
-    let mut _rounds = 0;
+            let mut simulation = Simulation::new(&seat_grid, &options.rules);
 
-    'mutate: loop {
-        _rounds += 1;
+            let outcome = simulation.run_observed(options.round_limit, &mut |simulation| {
+                let grid = simulation.grid();
+                let mut output = stdout();
 
-        let next_grid = previous_grid.apply_rules(&part1_seat_state_mapper);
+                // Drawn in one go to avoid flickering
+                let frame = format!("{0}{1}\nround {2}: {3} occupied\n", CLEAR_SCREEN, ansi_frame(&grid).join("\n"), simulation.round(), grid.num_occupied());
 
-        if previous_grid.seats == next_grid.seats {
-            break 'mutate;
+                output.write_all(frame.as_bytes())
+                    .and_then(|_| output.flush())
+                    .expect("Error while drawing frame");
+
+                thread::sleep(options.frame_delay);
+            });
+
+            print_outcome(&outcome, &simulation.grid());
         }
+        Some("export") => {
+            let (format, path) = match args.get(1..3) {
+                Some([format, path]) => (format.parse::<FrameFormat>().unwrap_or_else(|error| exit_with_usage(&error)), path),
+                _ => exit_with_usage("Missing frame format or path"),
+            };
 
-        previous_grid = next_grid;
-    }
+            let options = parse_options(&args[3..]).unwrap_or_else(|error| exit_with_usage(&error));
 
-    println!("part1 finished after {0} rounds of shuffling", _rounds);
+            let mut simulation = Simulation::new(&seat_grid, &options.rules);
 
-    let occupied_seats = previous_grid.seats.iter()
-        .filter(|&seat| *seat == SeatType::Occupied)
-        .count();
+            let outcome = match format {
+                FrameFormat::Text => {
+                    let mut output = BufWriter::new(File::create(path).expect("Error while creating frame file"));
 
-    (previous_grid, occupied_seats)
-}
+                    let outcome = simulation.run_observed(options.round_limit, &mut |simulation| {
+                        write_text_frame(simulation.round(), &simulation.grid(), &mut output)
+                            .expect("Error while writing frame");
+                    });
 
-fn part2_walk(seat_grid: &SeatGrid, x: GridDimensionType, y: GridDimensionType, dx: GridDimensionType, dy: GridDimensionType) -> usize {
-    let mut column = x + dx;
-    let mut row = y + dy;
+                    output.flush().expect("Error while writing frame");
 
-    while column >= 0 && column < seat_grid.width
-        && row >= 0 && row < seat_grid.height {
-        match seat_grid.seat_at(column, row).unwrap() {
-            SeatType::None => (),
-            SeatType::Empty => return 0,
-            SeatType::Occupied => return 1,
+                    outcome
+                }
+                FrameFormat::Ppm => simulation.run_observed(options.round_limit, &mut |simulation| {
+                    // One image per round: <path>0000.ppm, <path>0001.ppm, ...
+                    let file = File::create(format!("{0}{1:04}.ppm", path, simulation.round()))
+                        .expect("Error while creating frame file");
+
+                    write_ppm_frame(&simulation.grid(), BufWriter::new(file))
+                        .expect("Error while writing frame");
+                }),
+            };
+
+            print_outcome(&outcome, &simulation.grid());
         }
+        Some(command) => exit_with_usage(&format!("Unknown command: {0}", command)),
+    }
+}
 
-        column += dx;
-        row += dy;
+fn print_outcome(outcome: &Outcome, final_grid: &SeatGrid) {
+    match outcome {
+        Outcome::Cycle { length: 1, .. } =>
+            println!("simulate: stable after {0} rounds with {1} occupied seats", outcome.rounds(), final_grid.num_occupied()),
+        Outcome::Cycle { start, length, states } => {
+            let occupied: Vec<usize> = states.iter().map(SeatGrid::num_occupied).collect();
+            println!("simulate: entered a cycle of length {0} at round {1}; occupied seats per state {2:?}", length, start, occupied);
+        }
+        Outcome::RoundLimit(rounds) =>
+            println!("simulate: gave up after {0} rounds with {1} occupied seats", rounds, final_grid.num_occupied()),
     }
+}
 
-    0
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day11 [simulate|animate|export <text|ppm> <path>] [--neighborhood moore|von-neumann|line-of-sight[:<max distance>]] [--occupy <max occupied>] [--vacate <min occupied>] [--max-rounds <rounds>] [--delay <milliseconds>] < input");
+    process::exit(1);
 }
 
-fn part2_adjacency_count(seat_grid: &SeatGrid, x: GridDimensionType, y: GridDimensionType) -> usize {
-    part2_walk(seat_grid, x, y, -1, 0) // left
-        + part2_walk(seat_grid, x, y, 1, 0) // right
-        + part2_walk(seat_grid, x, y, 0, -1) // top
-        + part2_walk(seat_grid, x, y, 0, 1) // bottom
-        + part2_walk(seat_grid, x, y, -1, -1) // top-left
-        + part2_walk(seat_grid, x, y, 1, -1) // top-right
-        + part2_walk(seat_grid, x, y, -1, 1) // bottom-left
-        + part2_walk(seat_grid, x, y, 1, 1) // bottom-right
+/// How the `simulate`, `animate` and `export` commands run the simulation.
+#[derive(Debug, PartialEq)]
+struct Options {
+    rules: Rules,
+    round_limit: Option<usize>,
+    /// How long `animate` shows each round
+    frame_delay: Duration,
 }
 
-fn part2_seat_state_mapper(seat_grid: &SeatGrid, x: GridDimensionType, y: GridDimensionType) -> Option<SeatType> {
-    seat_grid.seat_at(x, y)
-        .map(|current_seat| {
-            // println!("seats={3:?} seat({0}, {1})={2:?}", x, y, current_seat, self.seats);
-            match current_seat {
-                SeatType::Empty => {
-                    let adjacent_occupied_seats = part2_adjacency_count(seat_grid, x, y);
-                    if adjacent_occupied_seats == 0 {
-                        SeatType::Occupied
-                    } else {
-                        current_seat.clone()
-                    }
-                }
-                SeatType::Occupied => {
-                    let adjacent_occupied_seats = part2_adjacency_count(seat_grid, x, y);
-                    if adjacent_occupied_seats >= 5 {
-                        SeatType::Empty
-                    } else {
-                        current_seat.clone()
-                    }
-                }
-                SeatType::None => SeatType::None,
-            }
-        })
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rules: Rules::part1(),
+            round_limit: None,
+            frame_delay: Duration::from_millis(100),
+        }
+    }
 }
 
-fn part2(seat_grid: &SeatGrid) -> (SeatGrid, usize) {
-    let mut previous_grid: SeatGrid = seat_grid.clone();
+/// Starts from the default options and applies the arguments on top.
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    for option in args.chunks(2) {
+        let value = match option {
+            [_, value] => value,
+            _ => return Err(format!("Missing value for {0}", option[0])),
+        };
+
+        let threshold = || value.parse::<usize>().map_err(|_| format!("Invalid threshold: {0}", value));
+
+        match option[0].as_str() {
+            "--neighborhood" => options.rules.neighborhood = value.parse()?,
+            "--occupy" => options.rules.occupy_threshold = threshold()?,
+            "--vacate" => options.rules.vacate_threshold = threshold()?,
+            "--max-rounds" => options.round_limit = Some(value.parse::<usize>().map_err(|_| format!("Invalid number of rounds: {0}", value))?),
+            "--delay" => options.frame_delay = Duration::from_millis(value.parse::<u64>().map_err(|_| format!("Invalid delay: {0}", value))?),
+            unknown => return Err(format!("Unknown option: {0}", unknown)),
+        }
+    }
 
-    let mut _rounds = 0;
+    Ok(options)
+}
 
-    'mutate: loop {
-        _rounds += 1;
+/// Applies the rules until a grid repeats, or at most `round_limit` times; returns the last grid and how it ended.
+fn simulate_until_stable(seat_grid: &SeatGrid, rules: &Rules, round_limit: Option<usize>) -> (SeatGrid, Outcome) {
+    let mut simulation = Simulation::new(seat_grid, rules);
 
-        let next_grid = previous_grid.apply_rules(&part2_seat_state_mapper);
+    let outcome = simulation.run(round_limit);
 
-        if previous_grid.seats == next_grid.seats {
-            break 'mutate;
-        }
+    (simulation.grid(), outcome)
+}
 
-        previous_grid = next_grid;
-    }
+fn part1(seat_grid: &SeatGrid) -> (SeatGrid, usize) {
+    let (final_grid, outcome) = simulate_until_stable(seat_grid, &Rules::part1(), None);
+
+    println!("part1 finished after {0} rounds of shuffling", outcome.rounds());
+
+    let occupied_seats = final_grid.num_occupied();
+
+    (final_grid, occupied_seats)
+}
+
+fn part2(seat_grid: &SeatGrid) -> (SeatGrid, usize) {
+    let (final_grid, outcome) = simulate_until_stable(seat_grid, &Rules::part2(), None);
 
-    println!("part2 finished after {0} rounds of shuffling", _rounds);
+    println!("part2 finished after {0} rounds of shuffling", outcome.rounds());
 
-    let occupied_seats = previous_grid.seats.iter()
-        .filter(|&seat| *seat == SeatType::Occupied)
-        .count();
+    let occupied_seats = final_grid.num_occupied();
 
-    (previous_grid, occupied_seats)
+    (final_grid, occupied_seats)
 }
 
 fn seat_grid_to_input_format(seat_grid: &SeatGrid) -> Vec<String> {
@@ -289,6 +388,13 @@ fn seat_grid_to_input_format(seat_grid: &SeatGrid) -> Vec<String> {
 mod tests {
     use super::*;
 
+    fn num_occupied_neighbors(seat_grid: &SeatGrid, x: GridDimensionType, y: GridDimensionType, neighborhood: &Neighborhood) -> usize {
+        seat_grid.neighbors(x, y, neighborhood).into_iter()
+            .filter(|&index| seat_grid.seats[index] == SeatType::Occupied)
+            .count()
+    }
+
+    #[allow(clippy::redundant_closure)]
     fn example1_data() -> Vec<String> {
         vec!["L.LL.LL.LL", "LLLLLLL.LL", "L.L.L..L..", "LLLL.LL.LL", "L.LL.LL.LL", "L.LLLLL.LL", "..L.L.....", "LLLLLLLLLL", "L.LLLLLL.L", "L.LLLLL.LL"]
             .into_iter()
@@ -323,6 +429,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_num_adjacent_occupied_returns_0_when_all_neighbors_empty() {
         let lines = vec!["LLL", "L#L", "LLL"]
             .into_iter()
@@ -331,12 +438,13 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let num_neighbors = seat_grid.num_adjacent_occupied(1, 1);
+        let num_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
 
         assert_eq!(num_neighbors, 0);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_num_adjacent_occupied_returns_0_when_all_neighbors_floor() {
         let lines = vec!["...", ".#.", "..."]
             .into_iter()
@@ -345,12 +453,13 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let num_neighbors = seat_grid.num_adjacent_occupied(1, 1);
+        let num_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
 
         assert_eq!(num_neighbors, 0);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_num_adjacent_occupied_returns_8_when_all_neighbors_occupied() {
         let lines = vec!["###", "###", "###"]
             .into_iter()
@@ -359,12 +468,13 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let num_neighbors = seat_grid.num_adjacent_occupied(1, 1);
+        let num_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
 
         assert_eq!(num_neighbors, 8);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_num_adjacent_occupied_returns_4_when_4_neighbors_occupied() {
         let lines = vec!["L#L", "###", "L#L"]
             .into_iter()
@@ -373,12 +483,13 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let num_neighbors = seat_grid.num_adjacent_occupied(1, 1);
+        let num_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
 
         assert_eq!(num_neighbors, 4);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_apply_rules_returns_occupied_when_no_neighbors() {
         let lines = vec!["LLL", "LLL", "LLL"]
             .into_iter()
@@ -387,12 +498,14 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let next_seat = part1_seat_state_mapper(&seat_grid, 1, 1);
+        let occupied_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
+        let next_seat = Rules::part1().next_state(seat_grid.seat_at(1, 1).unwrap(), occupied_neighbors);
 
-        assert_eq!(next_seat, Some(SeatType::Occupied));
+        assert_eq!(next_seat, SeatType::Occupied);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn seat_grid_apply_rules_returns_empty_for_at_least_4_occupied_neighbors() {
         let lines = vec!["#.#", ".#.", "#.#"]
             .into_iter()
@@ -401,15 +514,17 @@ mod tests {
 
         let seat_grid = SeatGrid::from(&lines);
 
-        let next_seat = part1_seat_state_mapper(&seat_grid, 1, 1);
+        let occupied_neighbors = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore);
+        let next_seat = Rules::part1().next_state(seat_grid.seat_at(1, 1).unwrap(), occupied_neighbors);
 
-        assert_eq!(next_seat, Some(SeatType::Empty));
+        assert_eq!(next_seat, SeatType::Empty);
     }
 
     macro_rules! seat_grid_apply_rules_tests {
     ($($name:ident: $value:expr,)*) => {
     $(
         #[test]
+        #[allow(clippy::redundant_closure)]
         fn $name() {
             let (input, expected) = $value;
 
@@ -417,7 +532,9 @@ mod tests {
             let expected = expected.into_iter().map(|str| String::from(str)).collect::<Vec<String>>();
 
             let seat_grid = SeatGrid::from(&input);
-            let result = seat_grid.apply_rules(&part1_seat_state_mapper);
+            let mut simulation = Simulation::new(&seat_grid, &Rules::part1());
+            simulation.step();
+            let result = simulation.grid();
 
             let expected_grid = SeatGrid::from(&expected);
             // println!("orig={0:?}", seat_grid_to_input_format(&result));
@@ -460,6 +577,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn part1_example1() {
         let seat_grid = SeatGrid::from(&example1_data());
         let (part1_result, _) = part1(&seat_grid);
@@ -478,6 +596,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn part1_works() {
         let input = vec![
             "LLLLL.LLLLLLLLLLLLLLLLLLLLLLLLLL.LLLLLLL.LLLLLLLLLL.LLLLLLLLLLL.LLLLLLLLLLLLLL.LLLL.LLLL.LLLLLL",
@@ -590,6 +709,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn part2_visibility_check1() {
         let input = vec![".......#.", "...#.....", ".#.......", ".........", "..#L....#", "....#....", ".........", "#........", "...#....."]
             .into_iter()
@@ -597,12 +717,13 @@ mod tests {
             .collect::<Vec<String>>();
 
         let seat_grid = SeatGrid::from(&input);
-        let adjacency_count = part2_adjacency_count(&seat_grid, 3, 4);
+        let adjacency_count = num_occupied_neighbors(&seat_grid, 3, 4, &Neighborhood::LineOfSight(None));
 
         assert_eq!(adjacency_count, 8);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn part2_visibility_check2() {
         let input = vec![".............", ".L.L.#.#.#.#.", "............."]
             .into_iter()
@@ -610,12 +731,13 @@ mod tests {
             .collect::<Vec<String>>();
 
         let seat_grid = SeatGrid::from(&input);
-        let adjacency_count = part2_adjacency_count(&seat_grid, 1, 1);
+        let adjacency_count = num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::LineOfSight(None));
 
         assert_eq!(adjacency_count, 0);
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn part2_visibility_check3() {
         let input = vec![".##.##.", "#.#.#.#", "##...##", "...L...", "##...##", "#.#.#.#", ".##.##."]
             .into_iter()
@@ -623,8 +745,80 @@ mod tests {
             .collect::<Vec<String>>();
 
         let seat_grid = SeatGrid::from(&input);
-        let adjacency_count = part2_adjacency_count(&seat_grid, 3, 3);
+        let adjacency_count = num_occupied_neighbors(&seat_grid, 3, 3, &Neighborhood::LineOfSight(None));
 
         assert_eq!(adjacency_count, 0);
     }
+
+    #[test]
+    fn part2_example1() {
+        let seat_grid = SeatGrid::from(&example1_data());
+        let (_part2_result, part2_occupied_seats) = part2(&seat_grid);
+
+        assert_eq!(part2_occupied_seats, 26);
+    }
+
+    #[test]
+    fn neighborhoods_count_different_cells() {
+        let input = vec!["#.#..", ".L..#", "#.#.."]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>();
+
+        let seat_grid = SeatGrid::from(&input);
+
+        assert_eq!(num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::Moore), 4);
+        assert_eq!(num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::VonNeumann), 0);
+        assert_eq!(num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::LineOfSight(None)), 5);
+        assert_eq!(num_occupied_neighbors(&seat_grid, 1, 1, &Neighborhood::LineOfSight(Some(2))), 4);
+    }
+
+    #[test]
+    fn neighborhood_from_str() {
+        assert_eq!("moore".parse(), Ok(Neighborhood::Moore));
+        assert_eq!("von-neumann".parse(), Ok(Neighborhood::VonNeumann));
+        assert_eq!("line-of-sight".parse(), Ok(Neighborhood::LineOfSight(None)));
+        assert_eq!("line-of-sight:3".parse(), Ok(Neighborhood::LineOfSight(Some(3))));
+        assert!("line-of-sight:0".parse::<Neighborhood>().is_err());
+        assert!("hexagonal".parse::<Neighborhood>().is_err());
+    }
+
+    #[test]
+    fn parse_options_overrides_part1_rules() {
+        let options: Vec<String> = vec!["--neighborhood", "line-of-sight", "--vacate", "5"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(parse_options(&options).map(|options| options.rules), Ok(Rules::part2()));
+        assert_eq!(parse_options(&[]), Ok(Options::default()));
+        assert!(parse_options(&options[..3]).is_err());
+
+        let options: Vec<String> = vec!["--max-rounds", "10", "--delay", "20"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(parse_options(&options), Ok(Options {
+            round_limit: Some(10),
+            frame_delay: Duration::from_millis(20),
+            ..Options::default()
+        }));
+    }
+
+    #[test]
+    fn simulate_with_custom_thresholds() {
+        // Seats fill up as long as at most one orthogonal neighbor is occupied and never empty again
+        let rules = Rules {
+            neighborhood: Neighborhood::VonNeumann,
+            occupy_threshold: 1,
+            vacate_threshold: 5,
+        };
+
+        let seat_grid = SeatGrid::from(&[String::from("LLLL")]);
+        let (final_grid, outcome) = simulate_until_stable(&seat_grid, &rules, None);
+
+        assert_eq!(seat_grid_to_input_format(&final_grid), vec!["####"]);
+        assert_eq!(outcome.rounds(), 2);
+    }
 }
@@ -0,0 +1,124 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{seat_grid_to_input_format, SeatGrid, SeatType};
+
+/// Clears the terminal and moves the cursor to the top left corner.
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// The width and height of a cell in PPM frames, in pixels.
+const PPM_CELL_SIZE: usize = 4;
+
+/// How frames are written for offline viewing.
+#[derive(Debug, PartialEq)]
+pub enum FrameFormat {
+    /// One file with every frame in the input format
+    Text,
+    /// One PPM image per frame
+    Ppm,
+}
+
+impl FromStr for FrameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(FrameFormat::Text),
+            "ppm" => Ok(FrameFormat::Ppm),
+            _ => Err(format!("Unknown frame format: {0} (expected text or ppm)", s)),
+        }
+    }
+}
+
+/// The grid as lines of colored text: occupied seats red, empty seats green and floor dimmed.
+pub fn ansi_frame(seat_grid: &SeatGrid) -> Vec<String> {
+    seat_grid_to_input_format(seat_grid).iter()
+        .map(|line| line.chars()
+            .map(|char| match char {
+                '#' => "\x1b[31m#",
+                'L' => "\x1b[32mL",
+                _ => "\x1b[2m.",
+            })
+            .chain(std::iter::once("\x1b[0m"))
+            .collect()
+        )
+        .collect()
+}
+
+/// Appends a frame to a text frame file: a header line, the grid in the input format and a blank line.
+pub fn write_text_frame<W: Write>(round: usize, seat_grid: &SeatGrid, mut output: W) -> io::Result<()> {
+    writeln!(output, "round {0}: {1} occupied", round, seat_grid.num_occupied())?;
+
+    for line in seat_grid_to_input_format(seat_grid) {
+        writeln!(output, "{0}", line)?;
+    }
+
+    writeln!(output)
+}
+
+fn seat_color(seat: &SeatType) -> [u8; 3] {
+    match seat {
+        SeatType::None => [40, 40, 40],
+        SeatType::Empty => [60, 180, 75],
+        SeatType::Occupied => [220, 50, 47],
+    }
+}
+
+/// Writes the grid as a binary PPM image with every cell a square of `PPM_CELL_SIZE` pixels.
+pub fn write_ppm_frame<W: Write>(seat_grid: &SeatGrid, mut output: W) -> io::Result<()> {
+    let width = seat_grid.width as usize;
+    let height = seat_grid.height as usize;
+
+    write!(output, "P6\n{0} {1}\n255\n", width * PPM_CELL_SIZE, height * PPM_CELL_SIZE)?;
+
+    for row in seat_grid.seats.chunks(width.max(1)) {
+        let pixels: Vec<u8> = row.iter()
+            .flat_map(|seat| seat_color(seat).repeat(PPM_CELL_SIZE))
+            .collect();
+
+        for _ in 0..PPM_CELL_SIZE {
+            output.write_all(&pixels)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(lines: &[&str]) -> SeatGrid {
+        let lines: Vec<String> = lines.iter().copied().map(String::from).collect();
+
+        SeatGrid::from(&lines)
+    }
+
+    #[test]
+    fn ansi_frame_colors_seats() {
+        assert_eq!(ansi_frame(&grid(&["#.L"])), vec!["\x1b[31m#\x1b[2m.\x1b[32mL\x1b[0m"]);
+    }
+
+    #[test]
+    fn write_text_frame_uses_the_input_format() {
+        let mut output: Vec<u8> = Vec::new();
+        write_text_frame(3, &grid(&["#.L", "LL#"]), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "round 3: 2 occupied\n#.L\nLL#\n\n");
+    }
+
+    #[test]
+    fn write_ppm_frame_scales_cells() {
+        let mut output: Vec<u8> = Vec::new();
+        write_ppm_frame(&grid(&["#.", "LL"]), &mut output).unwrap();
+
+        let header = format!("P6\n{0} {0}\n255\n", 2 * PPM_CELL_SIZE);
+        assert!(output.starts_with(header.as_bytes()));
+
+        let pixels = &output[header.len()..];
+        assert_eq!(pixels.len(), 2 * PPM_CELL_SIZE * 2 * PPM_CELL_SIZE * 3);
+        assert_eq!(pixels[..3], [220, 50, 47]);
+        assert_eq!(pixels[PPM_CELL_SIZE * 3..PPM_CELL_SIZE * 3 + 3], [40, 40, 40]);
+        assert_eq!(pixels[pixels.len() - 3..], [60, 180, 75]);
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{GridDimensionType, Rules, SeatGrid, SeatType};
+
+/// How a run ended.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The grid after round `start` (0 being the initial grid) came back `length` rounds later and repeats forever;
+    /// `states` are the grids of one period, starting with round `start`. A stable grid is a cycle of length 1.
+    Cycle {
+        start: usize,
+        length: usize,
+        states: Vec<SeatGrid>,
+    },
+    /// The round limit was reached before any grid repeated
+    RoundLimit(usize),
+}
+
+impl Outcome {
+    /// The number of rounds simulated, including the one that repeated an earlier grid.
+    pub fn rounds(&self) -> usize {
+        match self {
+            Outcome::Cycle { start, length, .. } => start + length,
+            Outcome::RoundLimit(rounds) => *rounds,
+        }
+    }
+}
+
+/// Runs the rules round by round on two reused seat buffers.
+///
+/// Floor never changes, so every seat's neighbors are looked up once up front, and every seat keeps a count of its
+/// occupied neighbors that is updated as seats change. A seat can only change if it or one of its neighbors changed in
+/// the previous round, so each round only evaluates the worklist built from the previous round's changes and costs
+/// O(changed seats) rather than O(cells).
+pub struct Simulation {
+    rules: Rules,
+    width: GridDimensionType,
+    height: GridDimensionType,
+    /// The indices of the seats counting as neighbors of all cells, one after another; floor has none
+    neighbors: Vec<usize>,
+    /// The neighbors of cell `index` are `neighbors[neighbor_offsets[index]..neighbor_offsets[index + 1]]`
+    neighbor_offsets: Vec<usize>,
+    /// At most eight, so a byte keeps the counts of large grids in cache
+    occupied_neighbors: Vec<u8>,
+    current: Vec<SeatType>,
+    /// The spare buffer, kept equal to `current` between rounds
+    next: Vec<SeatType>,
+    /// The cells to evaluate in the next round, each at most once
+    worklist: Vec<usize>,
+    /// Whether a cell is on the worklist
+    queued: Vec<bool>,
+    changed: Vec<usize>,
+    /// Hash of the occupied seats, updated with every change (see `seat_key`)
+    hash: u64,
+    rounds: usize,
+}
+
+impl Simulation {
+    pub fn new(seat_grid: &SeatGrid, rules: &Rules) -> Self {
+        let mut neighbors: Vec<usize> = Vec::with_capacity(8 * seat_grid.len());
+        let mut neighbor_offsets: Vec<usize> = Vec::with_capacity(seat_grid.len() + 1);
+        let mut occupied_neighbors: Vec<u8> = Vec::with_capacity(seat_grid.len());
+
+        neighbor_offsets.push(0);
+
+        for y in 0..seat_grid.height {
+            for x in 0..seat_grid.width {
+                if seat_grid.seat_at(x, y) != Some(&SeatType::None) {
+                    neighbors.extend(seat_grid.neighbors(x, y, &rules.neighborhood));
+                }
+
+                let seats = &neighbors[*neighbor_offsets.last().unwrap()..];
+
+                occupied_neighbors.push(seats.iter()
+                    .filter(|&&neighbor| seat_grid.seats[neighbor] == SeatType::Occupied)
+                    .count() as u8
+                );
+                neighbor_offsets.push(neighbors.len());
+            }
+        }
+
+        let worklist: Vec<usize> = (0..seat_grid.len())
+            .filter(|&index| seat_grid.seats[index] != SeatType::None)
+            .collect();
+
+        let mut queued = vec![false; seat_grid.len()];
+
+        for &index in &worklist {
+            queued[index] = true;
+        }
+
+        let hash = (0..seat_grid.len())
+            .filter(|&index| seat_grid.seats[index] == SeatType::Occupied)
+            .fold(0, |hash, index| hash ^ seat_key(index));
+
+        Self {
+            rules: rules.clone(),
+            width: seat_grid.width,
+            height: seat_grid.height,
+            neighbors,
+            neighbor_offsets,
+            occupied_neighbors,
+            current: seat_grid.seats.clone(),
+            next: seat_grid.seats.clone(),
+            worklist,
+            queued,
+            changed: Vec::new(),
+            hash,
+            rounds: 0,
+        }
+    }
+
+    /// Applies the rules once; returns whether any seat changed.
+    pub fn step(&mut self) -> bool {
+        self.rounds += 1;
+        self.changed.clear();
+
+        for &index in &self.worklist {
+            self.queued[index] = false;
+
+            let next_seat = self.rules.next_state(&self.current[index], self.occupied_neighbors[index] as usize);
+
+            if next_seat != self.current[index] {
+                self.next[index] = next_seat;
+                self.changed.push(index);
+            }
+        }
+
+        self.worklist.clear();
+
+        std::mem::swap(&mut self.current, &mut self.next);
+
+        // Brings the spare buffer, the counts and the hash up to date and queues every seat the changes can affect.
+        // Every neighborhood is symmetric, so the seats seeing a changed seat are exactly its own neighbors.
+        for &index in &self.changed {
+            self.next[index] = self.current[index].clone();
+            self.hash ^= seat_key(index);
+
+            let now_occupied = self.current[index] == SeatType::Occupied;
+
+            for &neighbor in &self.neighbors[self.neighbor_offsets[index]..self.neighbor_offsets[index + 1]] {
+                if now_occupied {
+                    self.occupied_neighbors[neighbor] += 1;
+                } else {
+                    self.occupied_neighbors[neighbor] -= 1;
+                }
+
+                enqueue(&mut self.worklist, &mut self.queued, neighbor);
+            }
+
+            enqueue(&mut self.worklist, &mut self.queued, index);
+        }
+
+        !self.changed.is_empty()
+    }
+
+    /// Steps until a grid repeats or `round_limit` rounds have been applied. Every grid seen is remembered by its
+    /// hash, so cycles of any length are found as soon as they close.
+    pub fn run(&mut self, round_limit: Option<usize>) -> Outcome {
+        self.run_observed(round_limit, &mut |_simulation| ())
+    }
+
+    /// Like `run`, calling `observe` with every grid before it is stepped, but not with the one that repeats.
+    ///
+    /// Earlier grids are not copied: each round only records which seats flipped, and an earlier grid is rebuilt from
+    /// those when its hash matches.
+    pub fn run_observed(&mut self, round_limit: Option<usize>, observe: &mut dyn FnMut(&Simulation)) -> Outcome {
+        let mut history: HashMap<u64, Vec<usize>> = HashMap::new();
+        // The rounds since the run started, which need not be round 0
+        let first_round = self.rounds;
+        // The seats that flipped in each round after `first_round`
+        let mut flips: Vec<Vec<usize>> = Vec::new();
+
+        loop {
+            let seen = history.entry(self.hash).or_default();
+
+            if let Some(&start) = seen.iter().find(|&&round| flipped_since(&flips[round - first_round..]).is_empty()) {
+                let mut seats = self.current.clone();
+                let mut states = Vec::with_capacity(self.rounds - start);
+
+                for index in flipped_since(&flips[start - first_round..]) {
+                    seats[index] = flip(&seats[index]);
+                }
+
+                for round in start..self.rounds {
+                    states.push(self.grid_with(seats.clone()));
+
+                    for &index in &flips[round - first_round] {
+                        seats[index] = flip(&seats[index]);
+                    }
+                }
+
+                return Outcome::Cycle {
+                    start,
+                    length: self.rounds - start,
+                    states,
+                };
+            }
+
+            seen.push(self.rounds);
+
+            observe(self);
+
+            if round_limit.map(|round_limit| self.rounds >= round_limit).unwrap_or(false) {
+                return Outcome::RoundLimit(self.rounds);
+            }
+
+            self.step();
+            flips.push(self.changed.clone());
+        }
+    }
+
+    /// The number of rounds applied so far.
+    pub fn round(&self) -> usize {
+        self.rounds
+    }
+
+    pub fn grid(&self) -> SeatGrid {
+        self.grid_with(self.current.clone())
+    }
+
+    fn grid_with(&self, seats: Vec<SeatType>) -> SeatGrid {
+        SeatGrid {
+            width: self.width,
+            height: self.height,
+            seats,
+        }
+    }
+}
+
+fn enqueue(worklist: &mut Vec<usize>, queued: &mut [bool], index: usize) {
+    if !queued[index] {
+        queued[index] = true;
+        worklist.push(index);
+    }
+}
+
+/// A pseudo-random key per cell (splitmix64); the grid hash is the XOR of the keys of all occupied seats, so flipping a
+/// seat updates it in constant time.
+fn seat_key(index: usize) -> u64 {
+    let mut key = (index as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+/// The seats that flipped an odd number of times over the given rounds, i.e. that differ between the grids before and
+/// after them.
+fn flipped_since(flips: &[Vec<usize>]) -> Vec<usize> {
+    let mut odd: HashSet<usize> = HashSet::new();
+
+    for &index in flips.iter().flatten() {
+        if !odd.remove(&index) {
+            odd.insert(index);
+        }
+    }
+
+    odd.into_iter().collect()
+}
+
+/// Seats only ever change between empty and occupied.
+fn flip(seat: &SeatType) -> SeatType {
+    match seat {
+        SeatType::Empty => SeatType::Occupied,
+        SeatType::Occupied => SeatType::Empty,
+        SeatType::None => SeatType::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::seat_grid_to_input_format;
+
+    fn grid(lines: &[&str]) -> SeatGrid {
+        let lines: Vec<String> = lines.iter().copied().map(String::from).collect();
+
+        SeatGrid::from(&lines)
+    }
+
+    #[test]
+    fn step_example1_part2_rounds() {
+        let mut simulation = Simulation::new(&grid(&["L.LL.LL.LL", "LLLLLLL.LL", "L.L.L..L..", "LLLL.LL.LL", "L.LL.LL.LL", "L.LLLLL.LL", "..L.L.....", "LLLLLLLLLL", "L.LLLLLL.L", "L.LLLLL.LL"]), &Rules::part2());
+
+        assert!(simulation.step());
+        assert!(simulation.step());
+        assert_eq!(seat_grid_to_input_format(&simulation.grid()), vec!["#.LL.LL.L#", "#LLLLLL.LL", "L.L.L..L..", "LLLL.LL.LL", "L.LL.LL.LL", "L.LLLLL.LL", "..L.L.....", "LLLLLLLLL#", "#.LLLLLL.L", "#.LLLLL.L#"]);
+
+        assert_eq!(simulation.run(None).rounds(), 7);
+        assert_eq!(simulation.grid().num_occupied(), 26);
+    }
+
+    #[test]
+    fn step_reevaluates_seats_that_changed_themselves() {
+        // Seats with no neighbors at all flip every round if vacating takes no more than occupying allows
+        let rules = Rules {
+            occupy_threshold: 0,
+            vacate_threshold: 0,
+            ..Rules::part1()
+        };
+
+        let mut simulation = Simulation::new(&grid(&["L.L"]), &rules);
+
+        for expected in &["#.#", "L.L", "#.#"] {
+            assert!(simulation.step());
+            assert_eq!(seat_grid_to_input_format(&simulation.grid()), vec![*expected]);
+        }
+    }
+
+    #[test]
+    fn run_finds_stable_grids() {
+        let mut simulation = Simulation::new(&grid(&["L.LL.LL.LL", "LLLLLLL.LL", "L.L.L..L..", "LLLL.LL.LL", "L.LL.LL.LL", "L.LLLLL.LL", "..L.L.....", "LLLLLLLLLL", "L.LLLLLL.L", "L.LLLLL.LL"]), &Rules::part1());
+
+        match simulation.run(None) {
+            Outcome::Cycle { start, length, states } => {
+                assert_eq!((start, length), (5, 1));
+                assert_eq!(states, vec![simulation.grid()]);
+                assert_eq!(states[0].num_occupied(), 37);
+            }
+            outcome => panic!("Unexpected outcome: {0:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn run_finds_oscillations() {
+        let rules = Rules {
+            occupy_threshold: 0,
+            vacate_threshold: 0,
+            ..Rules::part1()
+        };
+
+        let outcome = Simulation::new(&grid(&["L.L"]), &rules).run(None);
+
+        assert_eq!(outcome, Outcome::Cycle {
+            start: 0,
+            length: 2,
+            states: vec![grid(&["L.L"]), grid(&["#.#"])],
+        });
+        assert_eq!(outcome.rounds(), 2);
+    }
+
+    #[test]
+    fn run_observed_sees_every_grid_once() {
+        let mut rounds: Vec<(usize, Vec<String>)> = Vec::new();
+
+        Simulation::new(&grid(&["LL", "LL"]), &Rules::part1())
+            .run_observed(None, &mut |simulation| rounds.push((simulation.round(), seat_grid_to_input_format(&simulation.grid()))));
+
+        assert_eq!(rounds, vec![(0, vec![String::from("LL"), String::from("LL")]), (1, vec![String::from("##"), String::from("##")])]);
+    }
+
+    #[test]
+    fn run_stops_at_the_round_limit() {
+        let rules = Rules {
+            occupy_threshold: 0,
+            vacate_threshold: 0,
+            ..Rules::part1()
+        };
+
+        let mut simulation = Simulation::new(&grid(&["L.L"]), &rules);
+
+        assert_eq!(simulation.run(Some(1)), Outcome::RoundLimit(1));
+        assert_eq!(seat_grid_to_input_format(&simulation.grid()), vec!["#.#"]);
+    }
+
+    /// Applies the rules the way a plain simulation does: every round looks at every cell and its neighbors again, and
+    /// every grid is kept to find the first one that repeats. Returns the repeated grid and `Outcome::rounds`.
+    fn full_scan_until_repeated(seat_grid: &SeatGrid, rules: &Rules) -> (SeatGrid, usize) {
+        let mut seat_grid = seat_grid.clone();
+        let mut seen: HashSet<Vec<SeatType>> = HashSet::new();
+        let mut rounds = 0;
+
+        while seen.insert(seat_grid.seats.clone()) {
+            let mut next = seat_grid.clone();
+
+            for y in 0..seat_grid.height {
+                for x in 0..seat_grid.width {
+                    let index = (x + y * seat_grid.width) as usize;
+                    let occupied_neighbors = seat_grid.neighbors(x, y, &rules.neighborhood).into_iter()
+                        .filter(|&neighbor| seat_grid.seats[neighbor] == SeatType::Occupied)
+                        .count();
+
+                    next.seats[index] = rules.next_state(&seat_grid.seats[index], occupied_neighbors);
+                }
+            }
+
+            seat_grid = next;
+            rounds += 1;
+        }
+
+        (seat_grid, rounds)
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture` to see the timings.
+    #[test]
+    #[ignore]
+    fn worklist_beats_full_scans() {
+        let lines: Vec<String> = include_str!("../resources/input").lines()
+            .map(String::from)
+            .collect();
+        let seat_grid = SeatGrid::from(&lines);
+
+        for rules in &[Rules::part1(), Rules::part2()] {
+            let started = Instant::now();
+            let (expected, rounds) = full_scan_until_repeated(&seat_grid, rules);
+            let full_scan = started.elapsed();
+
+            // Looking up the neighbors once is the same work as a single full scan round
+            let mut simulation = Simulation::new(&seat_grid, rules);
+            let started = Instant::now();
+            let outcome = simulation.run(None);
+            let worklist = started.elapsed();
+
+            println!("{0:?}: {1} rounds, full scan {2:?}, worklist {3:?} ({4:.1}x faster)", rules.neighborhood, rounds, full_scan, worklist,
+                full_scan.as_secs_f64() / worklist.as_secs_f64());
+
+            assert_eq!(outcome.rounds(), rounds);
+            assert_eq!(simulation.grid(), expected);
+            // Typically 10 to 20 times faster on the bundled input; the margin keeps timing noise from failing the test
+            assert!(worklist * 5 < full_scan, "worklist {0:?} vs full scan {1:?}", worklist, full_scan);
+        }
+    }
+}
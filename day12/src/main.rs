@@ -1,6 +1,10 @@
+use std::env;
+use std::fmt;
 use std::io::{BufRead, stdin};
+use std::process;
+use std::str::FromStr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 enum Direction {
     North,
     East,
@@ -8,7 +12,7 @@ enum Direction {
     West,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct NavigationState {
     north: i32,
     east: i32,
@@ -23,178 +27,288 @@ impl NavigationState {
             facing: Direction::East,
         }
     }
-}
 
-fn main() {
-    let input: Vec<String> = stdin().lock().lines()
-        .map(|maybe_line| maybe_line.expect("Error while reading line"))
-        .collect();
+    /// Moves `distance` units in `direction`; `None` if the position does not fit into an `i32`.
+    fn advance(&mut self, direction: &Direction, distance: i32) -> Option<()> {
+        self.north = get_dy(direction).checked_mul(distance)?.checked_add(self.north)?;
+        self.east = get_dx(direction).checked_mul(distance)?.checked_add(self.east)?;
 
-    // println!("input={0:?}", input);
+        Some(())
+    }
+}
+
+/// A clockwise turn by a number of quarter turns; negative numbers turn counterclockwise.
+type QuarterTurns = i32;
+
+/// A single line of the navigation instructions.
+#[derive(Clone, Debug, PartialEq)]
+enum NavInstruction {
+    /// N, E, S or W and a distance
+    Move(Direction, i32),
+    /// L or R and an angle, normalized to clockwise quarter turns in `0..4`
+    Turn(QuarterTurns),
+    /// F and a number of times to move forward
+    Forward(i32),
+}
 
-    {
-        let ship_state = part1(&input);
-        let manhattan_distance = manhattan_distance(ship_state);
-        println!("part1: {0}", manhattan_distance);
+impl fmt::Display for NavInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavInstruction::Move(Direction::North, distance) => write!(f, "N{0}", distance),
+            NavInstruction::Move(Direction::East, distance) => write!(f, "E{0}", distance),
+            NavInstruction::Move(Direction::South, distance) => write!(f, "S{0}", distance),
+            NavInstruction::Move(Direction::West, distance) => write!(f, "W{0}", distance),
+            NavInstruction::Turn(quarter_turns) => write!(f, "R{0}", quarter_turns * 90),
+            NavInstruction::Forward(times) => write!(f, "F{0}", times),
+        }
     }
-    {
-        let waypoint = NavigationState {
-            north: 1,
-            east: 10,
-            facing: Direction::North,
-        };
+}
 
-        let ship_state = part2(&input, &NavigationState::new(), &waypoint);
-        let manhattan_distance = manhattan_distance(ship_state);
-        println!("part2: {0}", manhattan_distance);
+impl FromStr for NavInstruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let action = chars.next().ok_or_else(|| String::from("empty instruction"))?;
+
+        let argument = chars.as_str().parse::<i32>()
+            .map_err(|_| String::from("argument is not a number"))?;
+
+        let turn = |degrees: i32| quarter_turns(degrees)
+            .map(NavInstruction::Turn)
+            .ok_or_else(|| String::from(INVALID_ANGLE));
+
+        match action {
+            'N' => Ok(NavInstruction::Move(Direction::North, argument)),
+            'E' => Ok(NavInstruction::Move(Direction::East, argument)),
+            'S' => Ok(NavInstruction::Move(Direction::South, argument)),
+            'W' => Ok(NavInstruction::Move(Direction::West, argument)),
+            // Negated before normalizing, so that L-90 turns right
+            'L' => turn(argument.checked_neg().ok_or_else(|| String::from(INVALID_ANGLE))?),
+            'R' => turn(argument),
+            'F' => Ok(NavInstruction::Forward(argument)),
+            _ => Err(String::from("unknown action")),
+        }
     }
 }
 
-fn manhattan_distance(ship_state: NavigationState) -> i32 {
-    ship_state.north.abs() + ship_state.east.abs()
+/// An instruction that cannot be followed, with the 1-based line number it is on.
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    instruction: String,
+    reason: String,
 }
 
-fn part1(input: &Vec<String>) -> NavigationState {
-    let mut ship_state = NavigationState::new();
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {0}: {1}: {2}", self.line, self.instruction, self.reason)
+    }
+}
 
-    for instruction in input {
-        let instruction = instruction.trim();
+/// Following an instruction would move the ship or waypoint out of the `i32` range.
+#[derive(Debug, PartialEq)]
+struct NavigationError {
+    line: usize,
+    instruction: NavInstruction,
+}
 
-        if instruction.is_empty() {
-            continue;
-        }
+impl fmt::Display for NavigationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {0}: {1}: position out of range", self.line, self.instruction)
+    }
+}
 
-        let command = &instruction[..1];
-        let argument = instruction[1..].parse::<i32>()
-            .expect(format!("Could not convert to i32: {0}", instruction).as_str());
+/// What the instructions act on.
+#[derive(Debug, PartialEq)]
+enum MovementModel {
+    /// N/E/S/W move the ship, L/R turn it and F moves it the way it faces (part 1)
+    Ship,
+    /// N/E/S/W move the waypoint, L/R rotate it around the ship and F moves the ship towards it (part 2)
+    Waypoint,
+    /// N/E/S/W move the ship, L/R rotate the waypoint around the ship and F moves the ship towards it
+    Hybrid,
+}
 
-        println!("before {0}: ship={1:?}", instruction, ship_state);
-        match command {
-            "F" => {
-                ship_state.north += get_dy(&ship_state.facing) * argument;
-                ship_state.east += get_dx(&ship_state.facing) * argument;
-            }
-            "N" => ship_state.north += argument,
-            "E" => ship_state.east += argument,
-            "S" => ship_state.north -= argument,
-            "W" => ship_state.east -= argument,
-            "L" => ship_state.facing = turn(&ship_state.facing, -argument),
-            "R" => ship_state.facing = turn(&ship_state.facing, argument),
-            _ => panic!(format!("Invalid instruction: {0}", instruction)),
+impl FromStr for MovementModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ship" => Ok(MovementModel::Ship),
+            "waypoint" => Ok(MovementModel::Waypoint),
+            "hybrid" => Ok(MovementModel::Hybrid),
+            _ => Err(format!("Unknown movement model: {0} (expected ship, waypoint or hybrid)", s)),
         }
-        println!("after {0}: {1:?}", instruction, ship_state);
     }
-
-    ship_state
 }
 
-fn part2(input: &Vec<String>, ship_state: &NavigationState, waypoint: &NavigationState) -> NavigationState {
-    let mut ship_state = (*ship_state).clone();
-    let mut waypoint = (*waypoint).clone();
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    for instruction in input {
-        let instruction = instruction.trim();
+    let input: Vec<String> = stdin().lock().lines()
+        .map(|maybe_line| maybe_line.expect("Error while reading line"))
+        .collect();
 
-        if instruction.is_empty() {
-            continue;
-        }
+    // println!("input={0:?}", input);
 
-        let command = &instruction[..1];
-        let argument = instruction[1..].parse::<i32>()
-            .expect(format!("Could not convert to i32: {0}", instruction).as_str());
-
-        println!("before {0}: ship={1:?} waypoint={2:?}", instruction, ship_state, waypoint);
-        match command {
-            "F" => {
-                let dx = waypoint.east * argument;
-                let dy = waypoint.north * argument;
-                println!("Delta=({0}, {1})", dx, dy);
-                ship_state.north += dy;
-                ship_state.east += dx;
+    let instructions = parse_instructions(&input).unwrap_or_else(|error| {
+        eprintln!("{0}", error);
+        process::exit(1);
+    });
+
+    let exit_with_error = |error: NavigationError| -> NavigationState {
+        eprintln!("{0}", error);
+        process::exit(1);
+    };
+
+    match args.as_slice() {
+        [] => {
+            {
+                let ship_state = part1(&instructions).unwrap_or_else(exit_with_error);
+                let manhattan_distance = manhattan_distance(ship_state);
+                println!("part1: {0}", manhattan_distance);
             }
-            "N" => waypoint.north += argument,
-            "E" => waypoint.east += argument,
-            "S" => waypoint.north -= argument,
-            "W" => waypoint.east -= argument,
-            "L" => {
-                let dx = waypoint.east;
-                let dy = waypoint.north;
-                let (x, y) = rotate(dx, dy, argument);
-                waypoint.north = y;
-                waypoint.east = x;
-            },
-            "R" => {
-                let dx = waypoint.east;
-                let dy = waypoint.north;
-                let (x, y) = rotate(dx, dy, -argument);
-                waypoint.north = y;
-                waypoint.east = x;
+            {
+                let ship_state = part2(&instructions, &NavigationState::new(), &initial_waypoint()).unwrap_or_else(exit_with_error);
+                let manhattan_distance = manhattan_distance(ship_state);
+                println!("part2: {0}", manhattan_distance);
             }
-            _ => panic!(format!("Invalid instruction: {0}", instruction)),
         }
-        println!("after {0}: ship={1:?} waypoint={2:?}", instruction, ship_state, waypoint);
+        [command, model] if command == "navigate" => {
+            let model = model.parse::<MovementModel>().unwrap_or_else(|error| exit_with_usage(&error));
+
+            let ship_state = navigate(&instructions, &model, &NavigationState::new(), &initial_waypoint()).unwrap_or_else(exit_with_error);
+            println!("{0:?}: {1}", model, manhattan_distance(ship_state));
+        }
+        _ => exit_with_usage(&format!("Unknown command: {0}", args.join(" "))),
     }
+}
 
-    ship_state
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{0}", error);
+    eprintln!("usage: day12 [navigate ship|waypoint|hybrid] < input");
+    process::exit(1);
 }
 
-fn direction_to_degrees(facing: &Direction) -> i32 {
-    match facing {
-        Direction::North => 0,
-        Direction::East => 90,
-        Direction::South => 180,
-        Direction::West => 270,
-    }
+/// Parses every non-blank line, keeping the 1-based line number of each instruction.
+fn parse_instructions(input: &[String]) -> Result<Vec<(usize, NavInstruction)>, ParseError> {
+    input.iter()
+        .enumerate()
+        .map(|(line_index, line)| (line_index + 1, line.trim()))
+        .filter(|(_line, instruction)| !instruction.is_empty())
+        .map(|(line, instruction)| instruction.parse::<NavInstruction>()
+            .map(|parsed| (line, parsed))
+            .map_err(|reason| ParseError { line, instruction: String::from(instruction), reason })
+        )
+        .collect()
 }
 
-fn degrees_to_direction(degrees: &i32) -> Direction {
-    match degrees {
-        0 | 360 => Direction::North,
-        90 => Direction::East,
-        180 => Direction::South,
-        270 => Direction::West,
-        _ => panic!(format!("Cannot convert {0} degrees to direction", degrees)),
+/// The waypoint starts 10 units east and 1 unit north relative to the ship.
+fn initial_waypoint() -> NavigationState {
+    NavigationState {
+        north: 1,
+        east: 10,
+        facing: Direction::North,
     }
 }
 
-fn turn(facing: &Direction, degrees: i32) -> Direction {
-    let mut current_degrees = direction_to_degrees(facing);
+/// Wider than the coordinates, so it cannot overflow.
+fn manhattan_distance(ship_state: NavigationState) -> u64 {
+    u64::from(ship_state.north.unsigned_abs()) + u64::from(ship_state.east.unsigned_abs())
+}
+
+/// Follows the instructions from `ship_state`; the waypoint is relative to the ship and unused by `MovementModel::Ship`.
+fn navigate(instructions: &[(usize, NavInstruction)], model: &MovementModel, ship_state: &NavigationState, waypoint: &NavigationState) -> Result<NavigationState, NavigationError> {
+    let mut ship_state = ship_state.clone();
+    let mut waypoint = waypoint.clone();
 
-    print!("Turning {0:?}={1} for {2} degrees", facing, current_degrees, degrees);
+    for (line, instruction) in instructions {
+        // println!("before {0:?}: ship={1:?} waypoint={2:?}", instruction, ship_state, waypoint);
 
-    current_degrees = (degrees + current_degrees) % 360;
+        follow(instruction, model, &mut ship_state, &mut waypoint)
+            .ok_or_else(|| NavigationError { line: *line, instruction: instruction.clone() })?;
 
-    while current_degrees.is_negative() {
-        current_degrees += 360;
+        // println!("after {0:?}: ship={1:?} waypoint={2:?}", instruction, ship_state, waypoint);
     }
 
-    let facing = degrees_to_direction(&current_degrees);
+    Ok(ship_state)
+}
 
-    println!(" = {0:?}", facing);
+/// Applies a single instruction; `None` if a position does not fit into an `i32`.
+fn follow(instruction: &NavInstruction, model: &MovementModel, ship_state: &mut NavigationState, waypoint: &mut NavigationState) -> Option<()> {
+    match (instruction, model) {
+        (NavInstruction::Move(direction, distance), MovementModel::Waypoint) => waypoint.advance(direction, *distance)?,
+        (NavInstruction::Move(direction, distance), _) => ship_state.advance(direction, *distance)?,
+        (NavInstruction::Turn(quarter_turns), MovementModel::Ship) => ship_state.facing = turn(&ship_state.facing, *quarter_turns),
+        (NavInstruction::Turn(quarter_turns), _) => {
+            let (x, y) = rotate(waypoint.east, waypoint.north, *quarter_turns)?;
+            waypoint.north = y;
+            waypoint.east = x;
+        }
+        (NavInstruction::Forward(times), MovementModel::Ship) => {
+            let facing = ship_state.facing.clone();
+            ship_state.advance(&facing, *times)?;
+        }
+        (NavInstruction::Forward(times), _) => {
+            ship_state.north = waypoint.north.checked_mul(*times)?.checked_add(ship_state.north)?;
+            ship_state.east = waypoint.east.checked_mul(*times)?.checked_add(ship_state.east)?;
+        }
+    }
 
-    facing
+    Some(())
 }
 
-fn rotate(x: i32, y: i32, degrees: i32) -> (i32, i32) {
-    print!("Turning {0},{1} for {2} degrees", x, y, degrees);
+fn part1(instructions: &[(usize, NavInstruction)]) -> Result<NavigationState, NavigationError> {
+    navigate(instructions, &MovementModel::Ship, &NavigationState::new(), &initial_waypoint())
+}
+
+fn part2(instructions: &[(usize, NavInstruction)], ship_state: &NavigationState, waypoint: &NavigationState) -> Result<NavigationState, NavigationError> {
+    navigate(instructions, &MovementModel::Waypoint, ship_state, waypoint)
+}
 
-    let x = x as f32;
-    let y = y as f32;
+const INVALID_ANGLE: &str = "turns must be a multiple of 90 degrees";
 
-    let degrees: f32 = degrees as f32 * std::f32::consts::PI / 180.0;
+/// Any multiple of 90 degrees, including negative ones and full circles, as the equivalent clockwise quarter turns in
+/// `0..4`; `None` for anything else.
+fn quarter_turns(degrees: i32) -> Option<QuarterTurns> {
+    if degrees % 90 == 0 {
+        Some((degrees / 90).rem_euclid(4))
+    } else {
+        None
+    }
+}
 
-    let d_cos = degrees.cos();
-    let d_sin = degrees.sin();
+fn direction_to_quarter_turns(facing: &Direction) -> QuarterTurns {
+    match facing {
+        Direction::North => 0,
+        Direction::East => 1,
+        Direction::South => 2,
+        Direction::West => 3,
+    }
+}
 
-    let new_x = x * d_cos - y * d_sin;
-    let new_y = x * d_sin + y * d_cos;
+fn quarter_turns_to_direction(quarter_turns: QuarterTurns) -> Direction {
+    match quarter_turns.rem_euclid(4) {
+        0 => Direction::North,
+        1 => Direction::East,
+        2 => Direction::South,
+        _ => Direction::West,
+    }
+}
 
-    println!(" = {0},{1}", new_x, new_y);
+fn turn(facing: &Direction, quarter_turns: QuarterTurns) -> Direction {
+    quarter_turns_to_direction(direction_to_quarter_turns(facing) + quarter_turns)
+}
 
-    (
-        new_x.round() as i32,
-        new_y.round() as i32,
-    )
+/// Rotates (x, y) clockwise around the origin, exactly; `None` if a coordinate is `i32::MIN` and cannot be negated.
+fn rotate(x: i32, y: i32, quarter_turns: QuarterTurns) -> Option<(i32, i32)> {
+    match quarter_turns.rem_euclid(4) {
+        0 => Some((x, y)),
+        1 => Some((y, x.checked_neg()?)),
+        2 => Some((x.checked_neg()?, y.checked_neg()?)),
+        _ => Some((y.checked_neg()?, x)),
+    }
 }
 
 fn get_dx(direction: &Direction) -> i32 {
@@ -219,19 +333,22 @@ fn get_dy(direction: &Direction) -> i32 {
 mod tests {
     use super::*;
 
-    #[test]
-    fn part1_example1() {
+    fn example1() -> Vec<(usize, NavInstruction)> {
         let input: Vec<String> = r"F10
 N3
 F7
 R90
 F11
 ".split('\n')
-            .into_iter()
-            .map(|s| String::from(s))
+            .map(String::from)
             .collect();
 
-        let ship_state = part1(input, &NavigationState::new());
+        parse_instructions(&input).unwrap()
+    }
+
+    #[test]
+    fn part1_example1() {
+        let ship_state = part1(&example1()).unwrap();
         let result = manhattan_distance(ship_state);
 
         assert_eq!(result, 25);
@@ -239,16 +356,6 @@ F11
 
     #[test]
     fn part2_example1() {
-        let input: Vec<String> = r"F10
-N3
-F7
-R90
-F11
-".split('\n')
-            .into_iter()
-            .map(|s| String::from(s))
-            .collect();
-
         let ship_state = NavigationState::new();
         let waypoint = NavigationState {
             north: 1,
@@ -256,9 +363,89 @@ F11
             facing: Direction::North,
         };
 
-        let ship_state = part2(input, &ship_state, &waypoint);
+        let ship_state = part2(&example1(), &ship_state, &waypoint).unwrap();
         let result = manhattan_distance(ship_state);
 
         assert_eq!(result, 286);
     }
+
+    #[test]
+    fn hybrid_example1() {
+        // F10 -> (10, 100), N3 moves the ship -> (13, 100), F7 -> (20, 170), R90 -> waypoint (-10, 1), F11 -> (-90, 181)
+        let ship_state = navigate(&example1(), &MovementModel::Hybrid, &NavigationState::new(), &initial_waypoint()).unwrap();
+
+        assert_eq!((ship_state.north, ship_state.east), (-90, 181));
+    }
+
+    #[test]
+    fn nav_instruction_from_str() {
+        assert_eq!("N3".parse(), Ok(NavInstruction::Move(Direction::North, 3)));
+        assert_eq!("W-2".parse(), Ok(NavInstruction::Move(Direction::West, -2)));
+        assert_eq!("L90".parse(), Ok(NavInstruction::Turn(3)));
+        assert_eq!("R-90".parse(), Ok(NavInstruction::Turn(3)));
+        assert_eq!("L-450".parse(), Ok(NavInstruction::Turn(1)));
+        assert_eq!("F11".parse(), Ok(NavInstruction::Forward(11)));
+
+        // Multibyte characters are rejected rather than sliced through
+        assert_eq!("Ñ3".parse::<NavInstruction>(), Err(String::from("unknown action")));
+        assert_eq!("FÑ".parse::<NavInstruction>(), Err(String::from("argument is not a number")));
+    }
+
+    #[test]
+    fn rotate_is_exact_for_large_coordinates() {
+        assert_eq!(rotate(16_777_217, 3, 1), Some((3, -16_777_217)));
+        assert_eq!(rotate(16_777_217, 3, -1), Some((-3, 16_777_217)));
+        assert_eq!(rotate(16_777_217, 3, 6), Some((-16_777_217, -3)));
+        assert_eq!(rotate(i32::MIN, 3, 1), None);
+    }
+
+    #[test]
+    fn turns_normalize_multiples_of_90_degrees() {
+        assert_eq!(quarter_turns(-90), Some(3));
+        assert_eq!(quarter_turns(450), Some(1));
+        assert_eq!(quarter_turns(360), Some(0));
+        assert_eq!(quarter_turns(45), None);
+        assert_eq!(quarter_turns(370), None);
+
+        assert_eq!(turn(&Direction::East, -1), Direction::North);
+        assert_eq!(turn(&Direction::North, -6), Direction::South);
+    }
+
+    #[test]
+    fn invalid_turns_are_rejected_with_the_line_number() {
+        let input: Vec<String> = vec!["F10", "", "R-90", "L370"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(parse_instructions(&input), Err(ParseError {
+            line: 4,
+            instruction: String::from("L370"),
+            reason: String::from(INVALID_ANGLE),
+        }));
+
+        assert!(parse_instructions(&input[..3]).is_ok());
+
+        let input = vec![String::from("R45")];
+        assert_eq!(parse_instructions(&input).unwrap_err().to_string(), "line 1: R45: turns must be a multiple of 90 degrees");
+    }
+
+    #[test]
+    fn overflows_are_reported_with_the_line_number() {
+        let input: Vec<String> = vec!["F10", "N2147483647", "L90", "F1"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let instructions = parse_instructions(&input).unwrap();
+
+        // The waypoint overflows on line 2, the ship once it faces north on line 4
+        let error = part2(&instructions, &NavigationState::new(), &initial_waypoint()).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: N2147483647: position out of range");
+
+        let error = part1(&instructions).unwrap_err();
+        assert_eq!(error, NavigationError { line: 4, instruction: NavInstruction::Forward(1) });
+
+        let ship_state = NavigationState { north: i32::MIN, east: i32::MIN, facing: Direction::East };
+        assert_eq!(manhattan_distance(ship_state), 1 << 32);
+    }
 }